@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dfsdisc::dfs;
+
+fuzz_target!(|data: &[u8]| {
+	// Neither parser may panic, whatever the input
+	let _ = dfs::Disc::from_bytes(data);
+	let _ = dfs::Disc::repair(data);
+});
@@ -0,0 +1,66 @@
+//! Standard (RFC 4648) base64, for file contents held inline in manifests.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+	let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+	for chunk in data.chunks(3) {
+		let bits = chunk.iter().enumerate()
+			.fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - i * 8));
+		for i in 0..4 {
+			match i <= chunk.len() {
+				true => out.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3f] as char),
+				false => out.push('='),
+			}
+		}
+	}
+	out
+}
+
+/// Decodes base64, ignoring whitespace so that long runs can be wrapped.
+/// Returns `None` if `src` is not valid base64.
+pub fn decode(src: &str) -> Option<Vec<u8>> {
+	let symbols: Vec<u8> = src.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+	if symbols.len() % 4 != 0 {
+		return None;
+	}
+
+	let mut out = Vec::with_capacity(symbols.len() / 4 * 3);
+	for (n, quad) in symbols.chunks(4).enumerate() {
+		let is_last = n == symbols.len() / 4 - 1;
+		let padding = quad.iter().rev().take_while(|&&b| b == b'=').count();
+		if padding > 2 || (padding > 0 && !is_last) {
+			return None;
+		}
+
+		let mut bits = 0u32;
+		for &symbol in &quad[..4 - padding] {
+			let value = ALPHABET.iter().position(|&a| a == symbol)?;
+			bits = bits << 6 | value as u32;
+		}
+		bits <<= padding * 6;
+		out.extend_from_slice(&bits.to_be_bytes()[1 .. 4 - padding]);
+	}
+	Some(out)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		for (plain, encoded) in [
+			(&b""[..], ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"),
+			(b"foob", "Zm9vYg=="), (b"\xff\x00\xfe", "/wD+"),
+		] {
+			assert_eq!(encoded, encode(plain));
+			assert_eq!(Some(plain.to_vec()), decode(encoded));
+		}
+
+		assert_eq!(Some(b"foobar".to_vec()), decode("Zm9v\n\tYmFy\n"));
+		assert_eq!(None, decode("Zm9"));
+		assert_eq!(None, decode("Zg==Zm9v"));
+		assert_eq!(None, decode("Z!=="));
+	}
+}
@@ -0,0 +1,107 @@
+//! Splitting file addresses between their catalogue fields.
+//!
+//! A catalogue entry stores the low 16 bits of a file's load address,
+//! execution address and length (and the low 8 bits of its start sector) in
+//! their own fields. The top two bits of all four are packed into a single
+//! shared byte, from the top bit down: execution address, length, load
+//! address, start sector.
+
+/// Where each value's high bits sit in the shared byte.
+const EXEC_SHIFT: u32 = 6;
+const LEN_SHIFT: u32 = 4;
+const LOAD_SHIFT: u32 = 2;
+const SECTOR_SHIFT: u32 = 0;
+
+/// Joins the low 16 bits of an address or length with its two high bits.
+/// Only the bottom two bits of `bits` are used.
+pub fn decode(low16: u16, bits: u8) -> u32 {
+	(low16 as u32) | ((bits & 3) as u32) << 16
+}
+
+/// Splits an address or length into its low 16 bits and two high bits.
+/// Anything above bit 17 is dropped.
+pub fn encode(full: u32) -> (u16, u8) {
+	(full as u16, ((full >> 16) & 3) as u8)
+}
+
+/// Joins the low 8 bits of a start sector with its two high bits. Only the
+/// bottom two bits of `bits` are used.
+pub fn decode_sector(low8: u8, bits: u8) -> u16 {
+	(low8 as u16) | ((bits & 3) as u16) << 8
+}
+
+/// Splits a start sector into its low 8 bits and two high bits. Anything
+/// above bit 9 is dropped.
+pub fn encode_sector(sector: u16) -> (u8, u8) {
+	(sector as u8, ((sector >> 8) & 3) as u8)
+}
+
+/// The high bits of each value in a catalogue entry, as packed into one
+/// byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HighBits {
+	pub load: u8,
+	pub exec: u8,
+	pub len: u8,
+	pub sector: u8,
+}
+
+impl HighBits {
+	/// Unpacks the shared byte of a catalogue entry.
+	pub fn from_byte(byte: u8) -> HighBits {
+		let field = |shift: u32| (byte >> shift) & 3;
+		HighBits {
+			load: field(LOAD_SHIFT),
+			exec: field(EXEC_SHIFT),
+			len: field(LEN_SHIFT),
+			sector: field(SECTOR_SHIFT),
+		}
+	}
+
+	/// Packs the high bits into one byte. Only the bottom two bits of each
+	/// field are used.
+	pub fn to_byte(self) -> u8 {
+		(self.exec & 3) << EXEC_SHIFT
+		| (self.len & 3) << LEN_SHIFT
+		| (self.load & 3) << LOAD_SHIFT
+		| (self.sector & 3) << SECTOR_SHIFT
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn symmetry() {
+		for bits in 0..4u8 {
+			for &low in &[0x0000u16, 0x0001, 0x1900, 0x8023, 0xffff] {
+				let full = decode(low, bits);
+				assert_eq!(full >> 16, bits as u32);
+				assert_eq!((low, bits), encode(full));
+
+				let sector = decode_sector(low as u8, bits);
+				assert_eq!((low as u8, bits), encode_sector(sector));
+			}
+
+			// each field lands in its own pair of bits, and comes back out
+			for (i, field) in [
+				HighBits { load: bits, ..HighBits::default() },
+				HighBits { exec: bits, ..HighBits::default() },
+				HighBits { len: bits, ..HighBits::default() },
+				HighBits { sector: bits, ..HighBits::default() },
+			].iter().enumerate() {
+				let byte = field.to_byte();
+				let shift = [LOAD_SHIFT, EXEC_SHIFT, LEN_SHIFT, SECTOR_SHIFT][i];
+				assert_eq!(bits << shift, byte);
+				assert_eq!(*field, HighBits::from_byte(byte));
+			}
+		}
+
+		for byte in 0..=255u8 {
+			assert_eq!(byte, HighBits::from_byte(byte).to_byte());
+		}
+		assert_eq!(0x3ffff, decode(0xffff, 0xff));
+		assert_eq!((0x1234, 3), encode(0xf_1234));
+	}
+}
@@ -0,0 +1,490 @@
+use std::ops::Range;
+
+use ascii::AsciiStr;
+use arrayvec::ArrayVec;
+
+use crate::dfs::*;
+use crate::dfs::addr::{self, HighBits};
+use crate::support::*;
+
+/// The most files a DFS catalogue can hold.
+pub const MAX_FILES: u8 = 31;
+
+pub(super) type HeaderSectors = [u8; 0x200];
+
+/// Makes a quick guess at whether `bytes` could be a DFS disc image, for
+/// telling apart a damaged DFS image from something else entirely (ADFS,
+/// say, or not a disc image at all).
+///
+/// This checks that the catalogue sectors are there, that the file count
+/// byte is a multiple of 8, that the disc has at least 2 sectors, and that
+/// the title is printable ASCII up to where it ends. An
+/// image that passes can still fail to parse.
+pub fn looks_like_dfs(bytes: &[u8]) -> bool {
+	let header: &HeaderSectors = match bytes.as_min_slice() {
+		Ok(header) => header,
+		Err(_) => return false,
+	};
+
+	// a multiple of 8 can't be more than 31 files
+	let file_count = header[0x105];
+	let title_ok = header[0x000..0x008].iter()
+		.chain(&header[0x100..0x104])
+		.take_while(|&&b| b >= b' ')
+		.all(|&b| b < 0x7f);
+
+	file_count % 8 == 0
+		&& read_total_sectors(header) >= 2
+		&& title_ok
+}
+
+/// The catalogue of a DFS disc: everything held in its first two sectors.
+///
+/// The catalogue is split across both sectors. The first holds the start of
+/// the disc title and the name of each file; the second holds the rest of
+/// the title, the disc metadata and each file's addresses. A `Catalogue`
+/// puts these back together, so the offset arithmetic lives in one place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catalogue {
+	pub(super) title: DiscName,
+	/// The title as stored, so that its padding survives a round trip.
+	pub(super) title_bytes: [u8; 12],
+	pub(super) cycle: BCD,
+	pub(super) boot_option: BootOption,
+	pub(super) total_sectors: u16,
+	entries: ArrayVec<CatalogueEntry, { MAX_FILES as usize }>,
+}
+
+impl Catalogue {
+	/// Parses the catalogue at the start of a disc image. Only the first 512
+	/// bytes of `src` are read; file data is not checked.
+	///
+	/// # Errors
+	/// * [`DFSError::InputTooSmall(usize)`][DFSError]: `src` is shorter than
+	///   the two catalogue sectors.
+	/// * [`DFSError::InvalidDiscData(usize)`][DFSError]: the catalogue is
+	///   malformed. The attached `usize` is the offset of the bad data.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn from_bytes(src: &[u8]) -> Result<Catalogue, DFSError> {
+		let header: &HeaderSectors = src.as_min_slice()
+			.map_err(|_| DFSError::InputTooSmall(SECTOR_SIZE * 2))?;
+		Self::read(header, false)
+	}
+
+	/// Parses a catalogue from exactly its two sectors, such as the first
+	/// 512 bytes of each disc in a collection. File data isn't needed, so
+	/// entries may point past the end of `header`.
+	///
+	/// # Errors
+	/// [`DFSError::InvalidDiscData(usize)`][DFSError]: the catalogue is
+	/// malformed. The attached `usize` is the offset of the bad data.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn from_header(header: &[u8; 0x200]) -> Result<Catalogue, DFSError> {
+		Self::read(header, false)
+	}
+
+	pub(super) fn read(header: &HeaderSectors, strict: bool) -> Result<Catalogue, DFSError> {
+		let title = DiscName::try_from(raw_title(header).as_slice())
+			.map_err(|e| DFSError::InvalidDiscData(title_offset(e.position())))?;
+		if strict {
+			let end = title.as_bytes().len();
+			if let Some(pos) = garbage_in_padding(&title_bytes(header)[end..]) {
+				return Err(DFSError::InvalidDiscData(title_offset(end + pos)));
+			}
+		}
+		let title_bytes = stored_title(header, &title);
+
+		// Disc sector count calculation. We don't check this against the
+		// length of the image, as it's common to have this value declare all
+		// 40 or 80 tracks, for a disc image to then only include the ones
+		// containing file data. It can't be more than 80 tracks' worth,
+		// though, even if the 10 bits it's stored in go further.
+		let total_sectors = match read_total_sectors(header) {
+			n if n < 2 || n > Geometry::Tracks80.sectors() =>
+				return Err(DFSError::InvalidDiscData(0x107)),
+			n => n,
+		};
+
+		let boot_option = read_boot_option(header);
+
+		let cycle = {
+			const OFFSET : usize = 0x104;
+			BCD::from_hex(header[OFFSET])
+				.map_err(|_| DFSError::InvalidDiscData(OFFSET))?
+		};
+
+		let file_count = {
+			const OFFSET : usize = 0x105;
+			let raw = header[OFFSET];
+			if (raw & 0x07) != 0 { return Err(DFSError::InvalidDiscData(OFFSET)); }
+
+			raw >> 3
+		};
+
+		let entries = (0..file_count)
+			.map(|i| CatalogueEntry::read(header, i, strict))
+			.collect::<Result<_, _>>()?;
+
+		Ok(Catalogue { title, title_bytes, cycle, boot_option, total_sectors, entries })
+	}
+
+	/// Encodes the catalogue as the first two sectors of a disc image.
+	pub fn to_bytes(&self) -> [u8; 0x200] {
+		let mut buf = [0u8; 0x200];
+
+		buf[0x000..0x008].copy_from_slice(&self.title_bytes[..8]);
+		buf[0x100..0x104].copy_from_slice(&self.title_bytes[8..]);
+		buf[0x104] = self.cycle.into_hex();
+		buf[0x105] = self.file_count() * 8; // at most 31 files, so won't wrap
+		buf[0x106] = /* b4,5 = boot option  */ (self.boot_option as u8) << 4
+		           | /* b0,1 = sectors b8,9 */ ((self.total_sectors & 0x300) >> 8) as u8;
+		buf[0x107] = (self.total_sectors & 255) as u8;
+
+		for (i, entry) in self.entries.iter().enumerate() {
+			entry.write(&mut buf, i as u8);
+		}
+
+		buf
+	}
+
+	pub fn title(&self) -> &AsciiStr { self.title.as_ascii_str() }
+	pub fn cycle(&self) -> BCD { self.cycle }
+	pub fn boot_option(&self) -> BootOption { self.boot_option }
+	pub fn file_count(&self) -> u8 { self.entries.len() as u8 }
+	/// The number of sectors declared for the whole disc.
+	pub fn total_sectors(&self) -> u16 { self.total_sectors }
+	pub fn entries(&self) -> &[CatalogueEntry] { &self.entries }
+
+	pub(super) fn new(title: DiscName, cycle: BCD, boot_option: BootOption,
+		total_sectors: u16) -> Catalogue {
+		let title_bytes = padded_title(&title);
+		Catalogue { title, title_bytes, cycle, boot_option, total_sectors, entries: ArrayVec::new() }
+	}
+
+	/// Appends an entry. Panics if the catalogue is already full.
+	pub(super) fn push(&mut self, entry: CatalogueEntry) { self.entries.push(entry); }
+}
+
+/// A single file's entry in a [`Catalogue`](./struct.Catalogue.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogueEntry {
+	pub(super) name: FileName,
+	pub(super) dir: AsciiPrintingChar,
+	pub(super) locked: bool,
+	pub(super) load_addr: u32,
+	pub(super) exec_addr: u32,
+	pub(super) file_len: u32,
+	pub(super) start_sector: u32,
+}
+
+impl CatalogueEntry {
+	pub fn name(&self) -> &FileName { &self.name }
+	pub fn dir(&self) -> AsciiPrintingChar { self.dir }
+	pub fn is_locked(&self) -> bool { self.locked }
+	pub fn load_addr(&self) -> u32 { self.load_addr }
+	pub fn exec_addr(&self) -> u32 { self.exec_addr }
+	pub fn file_len(&self) -> u32 { self.file_len }
+	pub fn start_sector(&self) -> u16 { self.start_sector as u16 }
+
+	pub(super) fn for_file(file: &File, start_sector: u16) -> CatalogueEntry {
+		CatalogueEntry {
+			name: file.key().name.clone(),
+			dir: file.dir(),
+			locked: file.is_locked(),
+			load_addr: file.load_addr(),
+			exec_addr: file.exec_addr(),
+			file_len: file.content().len() as u32,
+			start_sector: start_sector as u32,
+		}
+	}
+
+	/// The byte holding the top two bits of the execution address, length,
+	/// load address and start sector, from the top bit down.
+	pub(super) fn busy_byte(&self) -> u8 {
+		HighBits {
+			load: addr::encode(self.load_addr).1,
+			exec: addr::encode(self.exec_addr).1,
+			len: addr::encode(self.file_len).1,
+			sector: addr::encode_sector(self.start_sector as u16).1,
+		}.to_byte()
+	}
+
+	/// Offset of the entry's name, in the first catalogue sector.
+	pub(super) fn offset1(index: u8) -> usize { (index as usize) * 8 + 0x008 }
+	/// Offset of the entry's addresses, in the second catalogue sector.
+	pub(super) fn offset2(index: u8) -> usize { (index as usize) * 8 + 0x108 }
+
+	/// Checks if both halves of entry `index` are all zero, as they are past
+	/// the end of a freshly formatted catalogue.
+	pub(super) fn is_blank(src: &HeaderSectors, index: u8) -> bool {
+		let (offset1, offset2) = (Self::offset1(index), Self::offset2(index));
+		src[offset1..offset1 + 8].iter().chain(&src[offset2..offset2 + 8]).all(|&b| b == 0)
+	}
+
+	pub(super) fn read(src: &HeaderSectors, index: u8, strict: bool) -> Result<CatalogueEntry, DFSError> {
+		// First half: filename, directory name, locked bit
+		let offset1 = Self::offset1(index);
+		// Second half: various addresses
+		let offset2 = Self::offset2(index);
+
+		// Set dir, locked
+		let (dir, locked) = {
+			let offset = offset1 + 7;
+			let raw = src[offset];
+
+			let dir = AsciiPrintingChar::from(raw & 0x7f)
+				.map_err(|_| DFSError::InvalidDiscData(offset))?;
+
+			(dir, raw > 0x7f)
+		};
+
+		let name = {
+			let name_buf = &src[offset1 .. (offset1 + 7)];
+			let name_len = name_buf.iter().take_while(|&&b| b > b' ').count();
+			FileName::try_from(&name_buf[..name_len]).map_err(|e| {
+				let str_pos = e.position();
+				DFSError::InvalidDiscData(offset1 + str_pos)
+			})?
+		};
+
+		if strict {
+			if let Some(pos) = name.iter().copied().position(super::file::is_reserved_in_name) {
+				return Err(DFSError::InvalidDiscData(offset1 + pos));
+			}
+			let end = name.len();
+			if let Some(pos) = garbage_in_padding(&src[offset1 + end .. offset1 + 7]) {
+				return Err(DFSError::InvalidDiscData(offset1 + end + pos));
+			}
+		}
+
+		let high = HighBits::from_byte(src[offset2 + 6]);
+
+		// Load/Exec
+		let load_addr = addr::decode(u16_from_le(&src[offset2 .. offset2 + 2]), high.load);
+		let exec_addr = addr::decode(u16_from_le(&src[offset2 + 2 .. offset2 + 4]), high.exec);
+
+		// File length and start sector
+		let file_len = addr::decode(u16_from_le(&src[offset2 + 4 .. offset2 + 6]), high.len);
+		let start_sector = addr::decode_sector(src[offset2 + 7], high.sector) as u32;
+
+		Ok(CatalogueEntry { name, dir, locked, load_addr, exec_addr, file_len, start_sector })
+	}
+
+	/// Reads entry `index` as `read` does without `strict`, but cuts its
+	/// name short at the first byte that can't be part of one, rather than
+	/// failing there. If anything but padding was cut off, also returns the
+	/// offset of the cut.
+	pub(super) fn read_truncating(src: &HeaderSectors, index: u8)
+	-> Result<(CatalogueEntry, Option<usize>), DFSError> {
+		let offset1 = Self::offset1(index);
+		let name_buf = &src[offset1 .. (offset1 + 7)];
+		let end = name_buf.iter()
+			.position(|&b| b == b' ' || AsciiPrintingChar::from(b).is_err())
+			.unwrap_or(name_buf.len());
+		// a name that's all garbage is better dropped than kept empty
+		if end == 0 || garbage_in_padding(&name_buf[end..]).is_none() {
+			return Self::read(src, index, false).map(|entry| (entry, None));
+		}
+
+		let mut patched = *src;
+		patched[offset1 + end .. offset1 + 7].fill(b' ');
+		Self::read(&patched, index, false).map(|entry| (entry, Some(offset1 + end)))
+	}
+
+	fn write(&self, dst: &mut HeaderSectors, index: u8) {
+		let offset1 = Self::offset1(index);
+		let offset2 = Self::offset2(index);
+
+		dst[offset1 .. offset1 + 7].copy_space_padded(self.name.as_bytes());
+		// directory, with the locked flag in the top bit
+		dst[offset1 + 7] = self.dir.as_byte() | if self.locked { 0x80 } else { 0 };
+
+		let (load, _) = addr::encode(self.load_addr);
+		let (exec, _) = addr::encode(self.exec_addr);
+		let (len, _) = addr::encode(self.file_len);
+		let (start, _) = addr::encode_sector(self.start_sector as u16);
+		dst[offset2 .. offset2 + 2].copy_from_slice(&load.to_le_bytes());
+		dst[offset2 + 2 .. offset2 + 4].copy_from_slice(&exec.to_le_bytes());
+		dst[offset2 + 4 .. offset2 + 6].copy_from_slice(&len.to_le_bytes());
+		dst[offset2 + 6] = self.busy_byte();
+		dst[offset2 + 7] = start;
+	}
+
+	/// The sectors this entry's data occupies. Empty files take up none.
+	pub(super) fn extent(&self) -> Range<u32> {
+		self.start_sector .. self.start_sector + (self.file_len as usize).sectors() as u32
+	}
+}
+
+/// The twelve title bytes, as stored.
+fn title_bytes(header: &HeaderSectors) -> [u8; 12] {
+	let mut buf = [0u8; 12];
+	buf[..8].copy_from_slice(&header[0x000..0x008]);
+	buf[8..].copy_from_slice(&header[0x100..0x104]);
+	buf
+}
+
+/// The twelve title bytes, cut short at the first control code, and without
+/// trailing spaces. Spaces within the title are kept, unless what follows
+/// the last of them isn't ASCII: a space then ends the title, as older
+/// images were written with anything after it.
+pub(super) fn raw_title(header: &HeaderSectors) -> ArrayVec<u8, 12> {
+	let mut title: ArrayVec<u8, 12> = title_bytes(header).iter()
+		.copied()
+		.take_while(|&b| b >= b' ')
+		.collect();
+	if let Some(bad) = title.iter().position(|&b| b > b'~') {
+		if let Some(space) = title[..bad].iter().rposition(|&b| b == b' ') {
+			title.truncate(space);
+		}
+	}
+	while title.last() == Some(&b' ') {
+		title.pop();
+	}
+	title
+}
+
+/// The title bytes to write back for `title`, read from `header`: as stored
+/// if they hold just `title` padded with spaces or `&00`, otherwise
+/// [padded with spaces](fn.padded_title.html).
+pub(super) fn stored_title(header: &HeaderSectors, title: &DiscName) -> [u8; 12] {
+	let stored = title_bytes(header);
+	let (name, padding) = stored.split_at(title.as_bytes().len());
+	if name == title.as_bytes() && garbage_in_padding(padding).is_none() {
+		stored
+	} else {
+		padded_title(title)
+	}
+}
+
+/// Finds the first byte in what should be the padding after a name that is
+/// neither a space nor `&00`, and so is probably left-over data.
+fn garbage_in_padding(padding: &[u8]) -> Option<usize> {
+	padding.iter().position(|&b| b != b' ' && b != 0)
+}
+
+/// The title as stored on disc: twelve bytes, padded with spaces.
+pub(super) fn padded_title(title: &DiscName) -> [u8; 12] {
+	let mut buf = [0u8; 12];
+	buf.copy_space_padded(title.as_bytes());
+	buf
+}
+
+/// Bits 0 and 1 of `&106` are bits 8 and 9 of the sector count; `&107` holds
+/// the rest.
+pub(super) fn read_total_sectors(header: &HeaderSectors) -> u16 {
+	((header[0x106] & 3) as u16) << 8 | header[0x107] as u16
+}
+
+/// The boot option is in bits 4 and 5 of `&106`. Every value of those two
+/// bits is a valid option.
+pub(super) fn read_boot_option(header: &HeaderSectors) -> BootOption {
+	match (header[0x106] >> 4) & 3 {
+		0 => BootOption::None,
+		1 => BootOption::Load,
+		2 => BootOption::Run,
+		_ => BootOption::Exec,
+	}
+}
+
+/// Converts a position in the disc title to its offset in the image.
+pub(super) fn title_offset(pos: usize) -> usize {
+	if pos >= 8 {
+		pos + 0xf8 // start of second sector; 0x008 -> 0x100
+	} else {
+		pos
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let mut header = [0u8; 0x200];
+		header[0x000..0x008].copy_from_slice(b"LONGDISC");
+		header[0x100..0x108].copy_from_slice(b"NAME\x42\x08\x13\x20");
+		header[0x008..0x010].copy_from_slice(b"FILE   \xc1");
+		header[0x108..0x110].copy_from_slice(b"\x00\x19\x23\x80\x01\x02\xa8\x02");
+
+		let catalogue = Catalogue::from_bytes(&header).unwrap();
+		assert_eq!("LONGDISCNAME", catalogue.title());
+		assert_eq!(BCD::from_hex(0x42).unwrap(), catalogue.cycle());
+		assert_eq!(BootOption::Load, catalogue.boot_option());
+		assert_eq!(800, catalogue.total_sectors());
+		assert_eq!(1, catalogue.file_count());
+
+		let entry = &catalogue.entries()[0];
+		assert_eq!("FILE", *entry.name());
+		assert_eq!(b'A', entry.dir().as_byte());
+		assert!(entry.is_locked());
+		assert_eq!(0x21900, entry.load_addr());
+		assert_eq!(0x28023, entry.exec_addr());
+		assert_eq!(0x20201, entry.file_len());
+		assert_eq!(2, entry.start_sector());
+
+		assert_eq!(&header[..], &catalogue.to_bytes()[..]);
+	}
+
+	#[test]
+	fn looks_like_dfs() {
+		let mut header = [0u8; 0x200];
+		header[0x000..0x008].copy_from_slice(b"TITLE\0\xff\xff");
+		header[0x105] = 0x10;
+		header[0x107] = 0x20;
+		assert!(super::looks_like_dfs(&header));
+		assert!(!super::looks_like_dfs(&header[..0x1ff]));
+
+		// xorshift, so the bytes are the same every time
+		let mut state = 0x2545_f491u32;
+		let noise: Vec<u8> = (0..0x200).map(|_| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state as u8
+		}).collect();
+		assert!(!super::looks_like_dfs(&noise));
+
+		header[0x105] = 0x0c;
+		assert!(!super::looks_like_dfs(&header));
+		header[0x105] = 0;
+		header[0x107] = 1;
+		assert!(!super::looks_like_dfs(&header));
+		header[0x107] = 2;
+		header[0x002] = 0x80;
+		assert!(!super::looks_like_dfs(&header));
+	}
+
+	#[test]
+	fn from_header() {
+		let mut header = [0u8; 0x200];
+		header[0x000..0x008].copy_from_slice(b"INDEXED ");
+		header[0x105] = 0x08;
+		header[0x106] = 0x03;
+		header[0x107] = 0x20;
+		header[0x008..0x010].copy_from_slice(b"FAR    $");
+		// starts at sector &31F, a long way past the header
+		header[0x108..0x110].copy_from_slice(b"\x00\x19\x00\x19\x00\x10\x03\x1f");
+
+		let catalogue = Catalogue::from_header(&header).unwrap();
+		assert_eq!("INDEXED", catalogue.title());
+		assert_eq!(0x320, catalogue.total_sectors());
+		assert_eq!(0x31f, catalogue.entries()[0].start_sector());
+		assert_eq!(0x1000, catalogue.entries()[0].file_len());
+	}
+
+	#[test]
+	fn title_offsets() {
+		let mut header = [0u8; 0x200];
+		header[0x000..0x008].copy_from_slice(b"ABCDEFGH");
+		header[0x100..0x104].copy_from_slice(b"AB\xffC");
+		header[0x107] = 2;
+		assert_eq!(Err(DFSError::InvalidDiscData(0x102)),
+			Catalogue::from_bytes(&header));
+		assert_eq!(Err(DFSError::InputTooSmall(0x200)),
+			Catalogue::from_bytes(&header[..0x1ff]));
+	}
+}
@@ -0,0 +1,209 @@
+//! Minimal reading of Opus DDOS double-density disc images.
+//!
+//! A DDOS disc has 18 sectors per track, and is split into up to eight
+//! volumes, `A` to `H`. Track 0 holds all of the catalogues:
+//!
+//! * Sectors `2n` and `2n+1` hold volume `n`'s catalogue, laid out as a
+//!   standard DFS catalogue. Start sectors are counted from the first track
+//!   of the volume.
+//! * Sector 16 holds the disc catalogue: `&20` at `&00`, the total sector
+//!   count (high byte first) at `&01`, the sectors per track (18) at `&03`,
+//!   and the number of tracks at `&04`. From `&08`, each volume has two
+//!   bytes, the first being its start track, or 0 if it isn't present.
+//!
+//! Only titles and file lists are read; nothing can be written.
+
+use std::borrow::Cow;
+
+use crate::dfs::*;
+use crate::support::*;
+
+use super::catalogue::HeaderSectors;
+
+const SECTORS_PER_TRACK: usize = 18;
+const DISC_CATALOGUE: usize = 16 * SECTOR_SIZE;
+const MAX_VOLUMES: usize = 8;
+
+/// The catalogue layouts that can be told apart from the start of an image.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CatalogueKind {
+	/// The standard Acorn DFS layout, read by [`Disc`](../struct.Disc.html).
+	Acorn,
+	/// Opus DDOS, read by [`ddos::Disc`](./struct.Disc.html).
+	OpusDDOS,
+}
+
+impl CatalogueKind {
+	/// Guesses the catalogue layout of a disc image.
+	///
+	/// An image is only taken to be DDOS if its disc catalogue is entirely
+	/// consistent, as a DFS file could otherwise happen to cover sector 16.
+	pub fn detect(src: &[u8]) -> CatalogueKind {
+		match disc_catalogue(src) {
+			Some(_) => CatalogueKind::OpusDDOS,
+			None => CatalogueKind::Acorn,
+		}
+	}
+}
+
+/// Reads the volume start tracks from the DDOS disc catalogue, if there
+/// is one.
+fn disc_catalogue(src: &[u8]) -> Option<[u8; MAX_VOLUMES]> {
+	let cat = src.get(DISC_CATALOGUE .. DISC_CATALOGUE + SECTOR_SIZE)?;
+	let total_sectors = u16::from_be_bytes([cat[1], cat[2]]) as usize;
+	let tracks = cat[4] as usize;
+
+	if cat[0] != 0x20 || cat[3] as usize != SECTORS_PER_TRACK
+	|| !matches!(tracks, 40 | 80) || total_sectors != tracks * SECTORS_PER_TRACK {
+		return None;
+	}
+
+	let mut starts = [0u8; MAX_VOLUMES];
+	for (i, start) in starts.iter_mut().enumerate() {
+		*start = cat[8 + i * 2];
+		if *start as usize >= tracks {
+			return None;
+		}
+	}
+	// volumes can't share track 0 with the catalogues
+	match starts.iter().any(|&s| s != 0) {
+		true => Some(starts),
+		false => None,
+	}
+}
+
+/// One volume of a DDOS disc.
+#[derive(Debug)]
+pub struct Volume<'d> {
+	letter: char,
+	title: DiscName,
+	files: Vec<File<'d>>,
+}
+
+impl<'d> Volume<'d> {
+	/// The volume's letter, from `A` to `H`.
+	pub fn letter(&self) -> char { self.letter }
+	pub fn title(&self) -> &DiscName { &self.title }
+	pub fn files(&self) -> &[File<'d>] { &self.files }
+}
+
+/// A DDOS disc image, read as a list of volumes.
+#[derive(Debug)]
+pub struct Disc<'d> {
+	volumes: Vec<Volume<'d>>,
+}
+
+impl<'d> Disc<'d> {
+	/// Reads the volumes of a DDOS disc image.
+	///
+	/// # Errors
+	/// * [`DFSError::WrongCatalogueKind`][DFSError]: `src` has no DDOS disc
+	///   catalogue.
+	/// * [`DFSError::InvalidDiscData(usize)`][DFSError]: a volume catalogue
+	///   is malformed, or a file runs past the end of `src`. The attached
+	///   `usize` is the offset of the bad data.
+	///
+	/// [DFSError]: ../enum.DFSError.html
+	pub fn from_bytes(src: &'d [u8]) -> Result<Disc<'d>, DFSError> {
+		let starts = disc_catalogue(src)
+			.ok_or(DFSError::WrongCatalogueKind(CatalogueKind::Acorn))?;
+
+		let mut volumes = Vec::new();
+		for (i, &start_track) in starts.iter().enumerate() {
+			if start_track == 0 {
+				continue;
+			}
+
+			let base = i * 2 * SECTOR_SIZE;
+			// the disc catalogue is further on, so this is in bounds
+			let header: &HeaderSectors = src[base..].as_min_slice()
+				.map_err(|_| DFSError::InputTooSmall(DISC_CATALOGUE))?;
+			let catalogue = Catalogue::read(header, false)
+				.map_err(|e| match e {
+					DFSError::InvalidDiscData(offset) => DFSError::InvalidDiscData(base + offset),
+					e => e,
+				})?;
+
+			let volume_start = start_track as usize * SECTORS_PER_TRACK * SECTOR_SIZE;
+			let mut files = Vec::with_capacity(catalogue.entries().len());
+			for (j, entry) in catalogue.entries().iter().enumerate() {
+				let data_start = volume_start + entry.start_sector() as usize * SECTOR_SIZE;
+				let data_end = data_start + entry.file_len() as usize;
+				let content = src.get(data_start..data_end)
+					.ok_or_else(|| DFSError::InvalidDiscData(base + 0x108 + j * 8 + 6))?;
+
+				let mut file = File::new(entry.name().clone(), entry.dir(),
+					entry.load_addr(), entry.exec_addr(), entry.is_locked(),
+					Cow::Borrowed(content));
+				file.set_catalogue_index(j as u8);
+				files.push(file);
+			}
+
+			volumes.push(Volume {
+				letter: (b'A' + i as u8) as char,
+				title: catalogue.title,
+				files,
+			});
+		}
+
+		Ok(Disc { volumes })
+	}
+
+	pub fn volumes(&self) -> &[Volume<'d>] { &self.volumes }
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::dfs::testing::*;
+
+	/// Builds an 80-track DDOS image, with volume A from track 1 holding
+	/// one file, and volume B from track 40 holding none.
+	fn ddos_image() -> Vec<u8> {
+		let files = [FileSpec { dir: b'$', name: b"HELLO", load_addr: 0x1900,
+			exec_addr: 0x1900, locked: false, content: b"hello" }];
+		let volume_a = make_image(&DiscSpec { name: b"VOLA", files: &files, ..Default::default() });
+		let volume_b = make_image(&DiscSpec { name: b"VOLB", ..Default::default() });
+
+		let mut image = vec![0u8; 80 * SECTORS_PER_TRACK * SECTOR_SIZE];
+		image[0x000..0x200].copy_from_slice(&volume_a[..0x200]);
+		image[0x200..0x400].copy_from_slice(&volume_b[..0x200]);
+		image[DISC_CATALOGUE .. DISC_CATALOGUE + 10]
+			.copy_from_slice(&[0x20, 0x05, 0xa0, 18, 80, 0, 0, 0, 1, 0]);
+		image[DISC_CATALOGUE + 10] = 40;
+
+		let data = (SECTORS_PER_TRACK + 2) * SECTOR_SIZE;
+		image[data .. data + 5].copy_from_slice(b"hello");
+		image
+	}
+
+	#[test]
+	fn detect() {
+		let image = ddos_image();
+		assert_eq!(CatalogueKind::OpusDDOS, CatalogueKind::detect(&image));
+		assert_eq!(Err(DFSError::WrongCatalogueKind(CatalogueKind::OpusDDOS)),
+			crate::dfs::Disc::from_bytes(&image).map(|_| ()));
+
+		let dfs_image = make_image(&DiscSpec::default());
+		assert_eq!(CatalogueKind::Acorn, CatalogueKind::detect(&dfs_image));
+		assert_eq!(Err(DFSError::WrongCatalogueKind(CatalogueKind::Acorn)),
+			Disc::from_bytes(&dfs_image).map(|_| ()));
+	}
+
+	#[test]
+	fn volumes() {
+		let image = ddos_image();
+		let disc = Disc::from_bytes(&image).unwrap();
+		let volumes = disc.volumes();
+		assert_eq!(2, volumes.len());
+
+		assert_eq!(('A', "VOLA"), (volumes[0].letter(), volumes[0].title().as_str()));
+		assert_eq!(1, volumes[0].files().len());
+		let file = &volumes[0].files()[0];
+		assert_eq!("HELLO", file.name());
+		assert_eq!(b"hello", file.content());
+
+		assert_eq!(('B', "VOLB"), (volumes[1].letter(), volumes[1].title().as_str()));
+		assert!(volumes[1].files().is_empty());
+	}
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::dfs::*;
+use crate::support::*;
+
+/// The name and directory of a file, as listed in a [`DiscDiff`].
+///
+/// [`DiscDiff`]: ./struct.DiscDiff.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileId {
+	pub dir: AsciiPrintingChar,
+	pub name: FileName,
+}
+
+impl FileId {
+	pub(super) fn of(file: &File) -> FileId {
+		FileId { dir: file.dir(), name: file.key().name.clone() }
+	}
+}
+
+impl fmt::Display for FileId {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}.{}", self.dir, self.name)
+	}
+}
+
+/// How a file present on both discs differs between them. Each field holds
+/// the old and new values, if they differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChanges {
+	pub file: FileId,
+	pub load_addr: Option<(u32, u32)>,
+	pub exec_addr: Option<(u32, u32)>,
+	pub locked: Option<(bool, bool)>,
+	/// Whether the file content differs.
+	pub content: bool,
+}
+
+/// The differences between two discs, as found by [`Disc::diff`].
+///
+/// Files are matched by name and directory. Where the disc's own attributes
+/// differ, the old and new values are held.
+///
+/// [`Disc::diff`]: ./struct.Disc.html#method.diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscDiff {
+	pub name: Option<(DiscName, DiscName)>,
+	pub boot_option: Option<(BootOption, BootOption)>,
+	pub cycle: Option<(BCD, BCD)>,
+	/// Files only on the new disc, in its catalogue order.
+	pub added: Vec<FileId>,
+	/// Files only on the old disc, in its catalogue order.
+	pub removed: Vec<FileId>,
+	/// Files on both discs that differ, in the old disc's catalogue order.
+	pub modified: Vec<FileChanges>,
+}
+
+impl DiscDiff {
+	/// Checks if the two discs were the same.
+	pub fn is_empty(&self) -> bool {
+		self.name.is_none() && self.boot_option.is_none() && self.cycle.is_none()
+			&& self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+	}
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+	if old == new { None } else { Some((old, new)) }
+}
+
+impl<'d> Disc<'d> {
+	/// Compares this disc with `other`, treating `self` as the old version.
+	///
+	/// Files are compared by load and execution address, locked flag and
+	/// content. Where on the disc a file's data lies is not compared.
+	pub fn diff(&self, other: &Disc) -> DiscDiff {
+		let mut modified = Vec::new();
+		let mut removed = Vec::new();
+
+		for old in self.files() {
+			let new = match other.find_file(&old.key().name, old.dir()) {
+				Some(f) => f,
+				None => { removed.push(FileId::of(old)); continue; },
+			};
+
+			let changes = FileChanges {
+				file: FileId::of(old),
+				load_addr: changed(old.load_addr(), new.load_addr()),
+				exec_addr: changed(old.exec_addr(), new.exec_addr()),
+				locked: changed(old.is_locked(), new.is_locked()),
+				content: old.content() != new.content(),
+			};
+			if changes.load_addr.is_some() || changes.exec_addr.is_some()
+			|| changes.locked.is_some() || changes.content {
+				modified.push(changes);
+			}
+		}
+
+		let added = other.files()
+			.filter(|f| self.find_file(&f.key().name, f.dir()).is_none())
+			.map(FileId::of)
+			.collect();
+
+		DiscDiff {
+			name: changed(&self.name, &other.name).map(|(a, b)| (a.clone(), b.clone())),
+			boot_option: changed(self.boot_option(), other.boot_option()),
+			cycle: changed(self.cycle(), other.cycle()),
+			added,
+			removed,
+			modified,
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::dfs::testing::*;
+
+	const FILES: [FileSpec<'static>; 3] = [
+		FileSpec { dir: b'$', name: b"SAME", load_addr: 0x1900, exec_addr: 0x1900,
+			locked: false, content: b"same" },
+		FileSpec { dir: b'$', name: b"CHANGE", load_addr: 0x1900, exec_addr: 0x1900,
+			locked: false, content: b"old" },
+		FileSpec::data(b'A', b"GONE", b""),
+	];
+
+	#[test]
+	fn diff() {
+		let old_image = make_image(&DiscSpec { name: b"OLD", files: &FILES, ..Default::default() });
+		let old = Disc::from_bytes(&old_image).unwrap();
+		assert!(old.diff(&old).is_empty());
+
+		let new_files = [
+			FILES[0],
+			FileSpec { load_addr: 0x2000, locked: true, content: b"new", ..FILES[1] },
+			FileSpec { dir: b'B', name: b"NEW", ..FILES[2] },
+		];
+		let new_image = make_image(&DiscSpec { name: b"NEW", cycle: 1, files: &new_files,
+			..Default::default() });
+		let new = Disc::from_bytes(&new_image).unwrap();
+
+		let diff = old.diff(&new);
+		assert!(!diff.is_empty());
+		let (old_name, new_name) = diff.name.unwrap();
+		assert_eq!(("OLD", "NEW"), (old_name.as_str(), new_name.as_str()));
+		assert_eq!(None, diff.boot_option);
+		assert_eq!(Some((BCD::C00, BCD::try_new(1).unwrap())), diff.cycle);
+		assert_eq!(["B.NEW"], &diff.added.iter().map(|f| f.to_string()).collect::<Vec<_>>()[..]);
+		assert_eq!(["A.GONE"], &diff.removed.iter().map(|f| f.to_string()).collect::<Vec<_>>()[..]);
+
+		assert_eq!(1, diff.modified.len());
+		let changes = &diff.modified[0];
+		assert_eq!("$.CHANGE", changes.file.to_string());
+		assert_eq!(Some((0x1900, 0x2000)), changes.load_addr);
+		assert_eq!(None, changes.exec_addr);
+		assert_eq!(Some((false, true)), changes.locked);
+		assert!(changes.content);
+	}
+}
@@ -1,17 +1,25 @@
 use std::borrow::Cow;
 use std::convert::TryFrom;
-use std::collections::HashSet;
 use std::io;
 use std::marker::PhantomData;
+use std::str::FromStr;
 
 use ascii::AsciiStr;
 use arrayvec::ArrayVec;
 
 use crate::dfs::*;
+use super::catalogue::{
+	HeaderSectors,
+	padded_title, raw_title, stored_title, read_boot_option, read_total_sectors, title_offset,
+};
 use crate::support::*;
 
 /// What a DFS-supporting OS would do with a [`Disc`](./struct.Disc.html)
 /// found in the drive during a Shift-BREAK.
+///
+/// This is set with `*OPT 4`, and stored in bits 4 and 5 of catalogue byte
+/// `&106`. Bits 0 and 1 of the same byte hold the top of the sector count;
+/// bits 2, 3, 6 and 7 are unused.
 #[derive(Debug, PartialEq, Clone, Copy, enum_utils::FromStr)]
 #[enumeration(case_insensitive)]
 #[repr(u8)]
@@ -31,6 +39,20 @@ impl BootOption {
 			Self::Exec => "exec",
 		}
 	}
+
+	/// Parses a boot option as a user would give it: either the number
+	/// passed to `*OPT 4` (`0` to `3`), or its name in any case.
+	///
+	/// # Errors
+	/// Returns [`DFSError::InvalidValue`][DFSError] if `s` is neither.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn from_opt4_arg(s: &str) -> Result<BootOption, DFSError> {
+		match u8::from_str(s) {
+			Ok(n) => BootOption::try_from(n),
+			Err(_) => BootOption::from_str(s).map_err(|_| DFSError::InvalidValue),
+		}
+	}
 }
 
 impl From<BootOption> for u8 {
@@ -51,23 +73,152 @@ impl TryFrom<u8> for BootOption {
 	}
 }
 
-const MAX_FILES: u8 = 31;
 const MAX_SECTORS: u16 = 800; // 10 sectors × 80 tracks
+const DEFAULT_SECTORS: u16 = 400; // 10 sectors × 40 tracks
+
+/// An order for [`Disc::sort_files`] to put the catalogue in.
+///
+/// Ties are broken by directory, then name.
+///
+/// [`Disc::sort_files`]: ./struct.Disc.html#method.sort_files
+#[derive(Debug, PartialEq, Eq, Clone, Copy, enum_utils::FromStr)]
+#[enumeration(case_insensitive, rename_all = "kebab-case")]
+pub enum SortKey {
+	/// By file name, then directory.
+	Name,
+	/// By directory, then file name.
+	Directory,
+	/// By where each file starts on disc, as given by
+	/// [`File::start_sector`](./struct.File.html#method.start_sector).
+	/// Files that haven't been placed come last.
+	StartSector,
+	/// By load address.
+	LoadAddr,
+	/// Back into the order of the catalogue the disc was read from. Files
+	/// that weren't read from an image come last.
+	CatalogueIndex,
+}
+
+impl SortKey {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Name           => "name",
+			Self::Directory      => "directory",
+			Self::StartSector    => "start-sector",
+			Self::LoadAddr       => "load-addr",
+			Self::CatalogueIndex => "catalogue-index",
+		}
+	}
+
+	fn compare(self, a: &File, b: &File) -> std::cmp::Ordering {
+		let first = match self {
+			Self::Name => a.key().name.as_ascii_str().cmp(b.key().name.as_ascii_str()),
+			Self::Directory => std::cmp::Ordering::Equal,
+			Self::StartSector => none_last(a.start_sector(), b.start_sector()),
+			Self::LoadAddr => a.load_addr().cmp(&b.load_addr()),
+			Self::CatalogueIndex => none_last(a.catalogue_index(), b.catalogue_index()),
+		};
+		first.then_with(|| a.key().cmp(b.key()))
+	}
+}
+
+/// What [`Disc::add_dir`] does when a host file's name, once cut down to
+/// fit DFS, is the same as a file it has already added or one already on
+/// the disc.
+///
+/// [`Disc::add_dir`]: ./struct.Disc.html#method.add_dir
+#[derive(Debug, PartialEq, Eq, Clone, Copy, enum_utils::FromStr)]
+#[enumeration(case_insensitive, rename_all = "kebab-case")]
+pub enum CollisionPolicy {
+	/// Fail without adding anything.
+	Error,
+	/// End the name with the lowest number that makes it unique, cutting it
+	/// shorter if need be: `LONGNAM`, then `LONGNA1`, `LONGNA2` and so on.
+	NumericSuffix,
+	/// Leave the later file out.
+	Skip,
+}
+
+impl CollisionPolicy {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Error         => "error",
+			Self::NumericSuffix => "numeric-suffix",
+			Self::Skip          => "skip",
+		}
+	}
+}
+
+fn none_last<T: Ord>(a: Option<T>, b: Option<T>) -> std::cmp::Ordering {
+	match (a, b) {
+		(Some(a), Some(b)) => a.cmp(&b),
+		(Some(_), None) => std::cmp::Ordering::Less,
+		(None, Some(_)) => std::cmp::Ordering::Greater,
+		(None, None) => std::cmp::Ordering::Equal,
+	}
+}
+
+/// The physical layout of one side of a DFS disc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Geometry {
+	/// 40 tracks of 10 sectors, or 100KiB.
+	Tracks40,
+	/// 80 tracks of 10 sectors, or 200KiB.
+	Tracks80,
+}
+
+impl Geometry {
+	pub fn tracks(self) -> u16 {
+		match self {
+			Self::Tracks40 => 40,
+			Self::Tracks80 => 80,
+		}
+	}
+
+	/// The number of sectors on one side of a disc with this geometry.
+	pub fn sectors(self) -> u16 { self.tracks() * 10 }
+
+	/// The smallest geometry with at least `sectors` sectors. Anything too
+	/// big for a 40-track disc is assumed to be 80 tracks.
+	pub fn for_sectors(sectors: u16) -> Geometry {
+		match sectors {
+			n if n <= Self::Tracks40.sectors() => Self::Tracks40,
+			_ => Self::Tracks80,
+		}
+	}
+}
+
+/// The byte a freshly formatted disc has in every data sector.
+const FORMAT_FILL: u8 = 0xe5;
 
-type HeaderSectors = [u8; 0x200];
 pub type DiscName = AsciiName<12>;
 
 /// Representation of a single-sided DFS disc.
-#[derive(Debug)]
+///
+/// Cloning a disc clones each of its files. Borrowed file content stays
+/// borrowed, so the clone is tied to the same source image.
+#[derive(Debug, Clone)]
 pub struct Disc<'d> {
 	_data: PhantomData<&'d [u8]>,
 
-	// TODO: hold tracks count
-
-	name: DiscName,
+	pub(super) name: DiscName,
+	/// The title as `to_image` writes it. Kept from the image the disc was
+	/// read from, so that its padding survives, until the name is changed.
+	title_bytes: [u8; 12],
 	boot_option: BootOption,
 	cycle: BCD,
-	files: HashSet<File<'d>>,
+	auto_increment_cycle: bool,
+	strict_names: bool,
+	total_sectors: u16,
+	/// Limits how far files can reach; `total_sectors` never goes past it.
+	/// Only set for a disc read from an image, or once set explicitly;
+	/// otherwise files can reach as far as an 80-track disc allows.
+	geometry: Option<Geometry>,
+	/// If set, `to_image` writes out every declared sector, filling the
+	/// unused space with this byte.
+	format_fill: Option<u8>,
+	/// In catalogue order; names are unique.
+	files: Vec<File<'d>>,
 }
 
 impl<'d> Disc<'d> {
@@ -75,18 +226,65 @@ impl<'d> Disc<'d> {
 	// Basic accessors
 	pub fn cycle(&self) -> BCD { self.cycle }
 	pub fn cycle_mut(&mut self) -> &mut BCD { &mut self.cycle }
+
+	/// Sets the cycle count from its decimal value, 0 to 99.
+	///
+	/// Unlike [`cycle_mut`](#method.cycle_mut), this needs no `BCD`: `42`
+	/// is stored as `&42`, as DFS would show it.
+	///
+	/// # Errors
+	/// [`BCDError::IntValueTooLarge`][BCDError] if `decimal` is over 99.
+	///
+	/// [BCDError]: ../support/enum.BCDError.html
+	pub fn set_cycle(&mut self, decimal: u8) -> Result<(), BCDError> {
+		self.cycle = BCD::try_new(decimal)?;
+		Ok(())
+	}
+
+	/// The cycle count as two decimal digits, e.g. `07`.
+	pub fn cycle_display(&self) -> String { self.cycle.to_string() }
 	pub fn increment_cycle(&mut self) {
+		self.cycle = self.next_cycle();
+	}
+
+	fn next_cycle(&self) -> BCD {
 		let next_cycle = self.cycle.into_u8().wrapping_add(1);
-		self.cycle = match BCD::try_new(next_cycle) {
+		match BCD::try_new(next_cycle) {
 			Ok(bcd) => bcd,
 			Err(_) => BCD::C00
-		};
+		}
+	}
+
+	/// Whether [`to_image`](#method.to_image) writes out the next cycle count
+	/// rather than the current one, as a real DFS would on every catalogue
+	/// write.
+	pub fn auto_increment_cycle(&self) -> bool { self.auto_increment_cycle }
+
+	/// Sets whether [`to_image`](#method.to_image) bumps the cycle count in
+	/// the image it writes. This is off by default.
+	///
+	/// The `Disc` itself is not changed by writing it out. If you call
+	/// [`increment_cycle`](#method.increment_cycle) as well, the written
+	/// cycle count will be two ahead of the original.
+	pub fn set_auto_increment_cycle(&mut self, on: bool) {
+		self.auto_increment_cycle = on;
 	}
 
 	pub fn name(&self) -> &AsciiStr { self.name.as_ascii_str() }
+
+	/// The title exactly as [`to_image`](#method.to_image) writes it: twelve
+	/// bytes, padded as in the image the disc was read from, or with spaces
+	/// for a new disc or a changed name. The first eight go in sector 0,
+	/// and the last four in sector 1.
+	pub fn raw_title_bytes(&self) -> [u8; 12] { self.title_bytes }
+
 	pub fn set_name(&mut self, new_name: &AsciiPrintingStr) -> Result<(), AsciiNameError> {
 		match AsciiName::try_from(new_name) {
-			Ok(n) => { self.name = n; Ok(()) },
+			Ok(n) => {
+				self.title_bytes = padded_title(&n);
+				self.name = n;
+				Ok(())
+			},
 			Err(e) => Err(e),
 		}
 	}
@@ -94,18 +292,149 @@ impl<'d> Disc<'d> {
 	pub fn boot_option(&self) -> BootOption { self.boot_option }
 	pub fn boot_option_mut(&mut self) -> &mut BootOption { &mut self.boot_option }
 
+	/// Sets the boot option from its name (`none`, `load`, `run` or `exec`,
+	/// in any case) or its number as given to `*OPT 4` (`0` to `3`).
+	///
+	/// # Errors
+	/// Returns [`DFSError::InvalidValue`][DFSError] if `s` is neither.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn set_boot_option_str(&mut self, s: &str) -> Result<(), DFSError> {
+		self.boot_option = BootOption::from_opt4_arg(s)?;
+		Ok(())
+	}
+
+	/// The number of sectors the catalogue declares for the whole disc.
+	///
+	/// This is written back by [`to_image`](#method.to_image), unless the
+	/// files on the disc need more space than this.
+	pub fn total_sectors(&self) -> u16 { self.total_sectors }
+
+	/// Sets the number of sectors the catalogue declares for the whole disc.
+	///
+	/// # Errors
+	/// Returns [`DFSError::InvalidValue`][DFSError] if `n` is too small to
+	/// hold the catalogue (2 sectors), or larger than an 80-track disc (800
+	/// sectors).
+	///
+	/// A 40-track disc becomes an 80-track one if `n` needs it to.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn set_total_sectors(&mut self, n: u16) -> Result<(), DFSError> {
+		match n {
+			2..=MAX_SECTORS => {
+				if self.geometry.is_some_and(|g| n > g.sectors()) {
+					self.geometry = Some(Geometry::for_sectors(n));
+				}
+				self.total_sectors = n;
+				Ok(())
+			},
+			_ => Err(DFSError::InvalidValue),
+		}
+	}
+
+	/// Whether [`add_file`](#method.add_file) rejects files whose names
+	/// can't be addressed by a real DFS.
+	pub fn strict_names(&self) -> bool { self.strict_names }
+
+	/// Sets whether [`add_file`](#method.add_file) rejects files whose names
+	/// can't be addressed by a real DFS (see
+	/// [`FileName::is_addressable`](./type.FileName.html#method.is_addressable)).
+	/// This is off by default.
+	pub fn set_strict_names(&mut self, on: bool) {
+		self.strict_names = on;
+	}
+
 	/// Creates a new, empty DFS disc.
+	///
+	/// The disc declares a 40-track single-sided geometry (400 sectors).
 	pub fn new() -> Disc<'d> {
 		Disc {
 			_data: PhantomData,
 
 			name: DiscName::empty(),
+			title_bytes: [b' '; 12],
 			boot_option: BootOption::None,
 			cycle: BCD::C00,
-			files: HashSet::new(),
+			auto_increment_cycle: false,
+			strict_names: false,
+			total_sectors: DEFAULT_SECTORS,
+			geometry: None,
+			format_fill: None,
+			files: Vec::new(),
+		}
+	}
+
+	/// Creates a new, empty DFS disc, as if freshly formatted with the
+	/// given geometry.
+	///
+	/// The disc declares every sector of `geometry`. When written out with
+	/// [`to_image`](#method.to_image), the image covers all of them, with
+	/// unused space filled with `0xE5` as a real format would leave it.
+	pub fn new_formatted(geometry: Geometry) -> Disc<'d> {
+		Disc {
+			total_sectors: geometry.sectors(),
+			geometry: Some(geometry),
+			format_fill: Some(FORMAT_FILL),
+			.. Self::new()
 		}
 	}
 
+	/// Creates a disc holding `files`, in the order given.
+	///
+	/// # Errors
+	/// * [`DFSError::DuplicateFileName`][DFSError]: two of the files have
+	///   the same name and directory.
+	/// * [`DFSError::CatalogueFull`][DFSError]: there are more files than a
+	///   catalogue can hold.
+	/// * [`DFSError::InvalidValue`][DFSError]: a file is in a directory
+	///   reserved by DFS.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn with_files<I>(name: DiscName, boot_option: BootOption, cycle: BCD, files: I)
+	-> Result<Disc<'d>, DFSError>
+	where I: IntoIterator<Item = File<'d>> {
+		let mut disc = Disc { title_bytes: padded_title(&name), name, boot_option, cycle, .. Self::new() };
+		for file in files {
+			if disc.files.contains(&file) {
+				return Err(DFSError::DuplicateFileName(format!("{}.{}", file.dir(), file.name())));
+			}
+			disc.add_file(file).map_err(|(e, _)| e)?;
+		}
+		Ok(disc)
+	}
+
+	/// The geometry of the disc. For a disc read from an image, this is the
+	/// smallest that holds the sectors its catalogue declares. A new disc
+	/// has no fixed geometry until one is [set](#method.set_geometry), so
+	/// this is the smallest that holds its declared sectors and its files.
+	pub fn geometry(&self) -> Geometry {
+		self.geometry.unwrap_or_else(|| {
+			let needed = (2 + self.sectors_used()).max(self.total_sectors as u32);
+			Geometry::for_sectors(needed.min(MAX_SECTORS as u32) as u16)
+		})
+	}
+
+	/// The number of tracks on the disc: 40 or 80. If the geometry is fixed,
+	/// [`to_image`](#method.to_image) won't write files past the last one.
+	pub fn track_count(&self) -> u16 { self.geometry().tracks() }
+
+	/// Fixes the geometry of the disc, without changing the number of
+	/// sectors it declares.
+	///
+	/// # Errors
+	/// Returns [`DFSError::InvalidValue`][DFSError] if the disc declares
+	/// more sectors than `geometry` has.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn set_geometry(&mut self, geometry: Geometry) -> Result<(), DFSError> {
+		if self.total_sectors > geometry.sectors() {
+			return Err(DFSError::InvalidValue);
+		}
+		self.geometry = Some(geometry);
+		Ok(())
+	}
+
 	/// Decodes a slice of bytes from a disc image into a `Disc`.
 	///
 	/// As DFS discs could only reach 200KiB in size, there is no provision
@@ -113,14 +442,16 @@ impl<'d> Disc<'d> {
 	///
 	/// # Errors
 	/// * [`DFSError::InputTooSmall(usize)`][DFSError]: `src` was too small
-	/// to be a valid DFS disc image. The attached `usize` indicates the
-	/// minimum correct size in bytes, which is 512.
+	///   to be a valid DFS disc image. The attached `usize` indicates the
+	///   minimum correct size in bytes, which is 512.
 	/// * [`DFSError::InvalidDiscData(usize)`][DFSError]: `src` did not
-	/// decode to a valid DFS disc. The attached `usize` is an offset into
-	/// `src` where the offending data was found.
+	///   decode to a valid DFS disc. The attached `usize` is an offset into
+	///   `src` where the offending data was found.
 	/// * [`DFSError::DuplicateFileName`][DFSError]: Two files were found
-	/// with the same name and directory entry. Whether these two files point
-	/// to the same on-disc data is not checked.
+	///   with the same name and directory entry. Whether these two files point
+	///   to the same on-disc data is not checked.
+	/// * [`DFSError::WrongCatalogueKind`][DFSError]: `src` looks like an
+	///   Opus DDOS image; use [`ddos::Disc`](./ddos/struct.Disc.html).
 	///
 	/// [DFSError]: ./enum.DFSError.html
 	///
@@ -133,230 +464,937 @@ impl<'d> Disc<'d> {
 	///
 	/// let mut disc_bytes = Vec::new();
 	/// {
-	/// 	let mut file = File::open("dfsimage.ssd").unwrap();
-	/// 	file.read_to_end(&mut disc_bytes).unwrap();
+	///     let mut file = File::open("dfsimage.ssd").unwrap();
+	///     file.read_to_end(&mut disc_bytes).unwrap();
 	/// }
 	///
 	/// let disc = match dfs::Disc::from_bytes(disc_bytes.as_slice()) {
-	/// 	Ok(x) => {
-	/// 		x
-	/// 	},
-	/// 	Err(e) => {
-	/// 		println!("Error parsing disc: {:?}", e);
-	/// 		return;
-	/// 	}
+	///     Ok(x) => {
+	///         x
+	///     },
+	///     Err(e) => {
+	///         println!("Error parsing disc: {:?}", e);
+	///         return;
+	///     }
 	/// };
 	///
 	/// println!("Files in {}:", disc.name());
 	/// for file in disc.files() {
-	/// 	println!("--> {}", file);
+	///     println!("--> {}", file);
 	/// }
 	/// ```
 	pub fn from_bytes(src: &'d [u8]) -> Result<Disc<'d>, DFSError> {
+		Self::decode(src, false)
+	}
+
+	/// Decodes a disc image like [`from_bytes`](#method.from_bytes), but
+	/// also rejects file names that a real DFS couldn't address, and
+	/// anything but spaces or `&00` after the end of the title or a file name.
+	/// `from_bytes` ignores whatever follows the end of a name, which can
+	/// hide left-over data.
+	///
+	/// The returned `Disc` has [strict names](#method.set_strict_names)
+	/// turned on.
+	///
+	/// # Errors
+	/// As for `from_bytes`. A file name containing a reserved character, or
+	/// a name followed by garbage, is reported as
+	/// [`DFSError::InvalidDiscData`][DFSError], with the offset of the
+	/// offending byte.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn from_bytes_strict(src: &'d [u8]) -> Result<Disc<'d>, DFSError> {
+		Self::decode(src, true)
+	}
+
+	fn decode(src: &'d [u8], strict: bool) -> Result<Disc<'d>, DFSError> {
+		let header_sectors: &HeaderSectors = src.as_min_slice().map_err(|_| DFSError::InputTooSmall(SECTOR_SIZE * 2))?;
+		// a DDOS image's first volume looks like a DFS catalogue, but isn't
+		if let kind @ CatalogueKind::OpusDDOS = CatalogueKind::detect(src) {
+			return Err(DFSError::WrongCatalogueKind(kind));
+		}
+		let catalogue = Catalogue::read(header_sectors, strict)?;
+		let files = populate_files(src, &catalogue)?;
+
+		let disc = Disc {
+			_data: PhantomData,
+			name: catalogue.title,
+			title_bytes: catalogue.title_bytes,
+			files,
+			boot_option: catalogue.boot_option,
+			cycle: catalogue.cycle,
+			auto_increment_cycle: false,
+			strict_names: strict,
+			total_sectors: catalogue.total_sectors,
+			geometry: Some(Geometry::for_sectors(catalogue.total_sectors)),
+			format_fill: None,
+		};
+
+		Ok(disc)
+	}
+
+	/// Reads a disc image from `reader`, refusing to read more than
+	/// `max_len` bytes. The returned `Disc` owns all of its file content.
+	///
+	/// A single-sided 80-track image is 200KiB; double-sided images, or
+	/// containers holding several discs, can be much larger. Pass a limit
+	/// suited to the images you expect, such as
+	/// [`MAX_DISC_SIZE`](./constant.MAX_DISC_SIZE.html).
+	///
+	/// # Errors
+	/// * [`DFSError::InputTooLarge(usize)`][DFSError]: `reader` held more
+	///   than `max_len` bytes. The attached `usize` is `max_len`.
+	/// * [`DFSError::Io`][DFSError]: reading failed.
+	/// * Anything [`from_bytes`](#method.from_bytes) can return.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn from_reader<R: io::Read>(reader: R, max_len: u64) -> Result<Disc<'static>, DFSError> {
+		let mut data = Vec::new();
+		io::Read::read_to_end(&mut reader.take(max_len.saturating_add(1)), &mut data)?;
+		if data.len() as u64 > max_len {
+			return Err(DFSError::InputTooLarge(max_len as usize));
+		}
+
+		Disc::from_bytes(&data).map(Disc::into_owned)
+	}
+
+	/// Copies any borrowed file content, so the disc no longer depends on
+	/// the image it was read from. See
+	/// [`File::into_owned`](./struct.File.html#method.into_owned).
+	pub fn into_owned(self) -> Disc<'static> {
+		Disc {
+			_data: PhantomData,
+			name: self.name,
+			title_bytes: self.title_bytes,
+			boot_option: self.boot_option,
+			cycle: self.cycle,
+			auto_increment_cycle: self.auto_increment_cycle,
+			strict_names: self.strict_names,
+			total_sectors: self.total_sectors,
+			geometry: self.geometry,
+			format_fill: self.format_fill,
+			files: self.files.into_iter().map(File::into_owned).collect(),
+		}
+	}
+
+	/// Decodes a damaged disc image as best it can, returning the recovered
+	/// disc and a list of what had to be changed to recover it.
+	///
+	/// This is for rescuing images that [`from_bytes`](#method.from_bytes)
+	/// rejects. Catalogue entries that can't be made sense of are dropped;
+	/// files that run past the end of `src` are cut short.
+	///
+	/// # Errors
+	/// Only [`DFSError::InputTooSmall`][DFSError], if `src` can't even hold
+	/// a catalogue.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn repair(src: &'d [u8]) -> Result<(Disc<'d>, Vec<RepairAction>), DFSError> {
 		let header_sectors: &HeaderSectors = src.as_min_slice().map_err(|_| DFSError::InputTooSmall(SECTOR_SIZE * 2))?;
+		let mut actions = Vec::new();
 
 		let disc_name = {
-			let buf = {
-				// 12 bytes of u8
-				// First 8 come from buf[0x000..0x008]
-				// Second 4 come from buf[0x100..0x104]
-				// We already know the source is big enough
-				let mut b: [u8; 12] = [0; 12];
-				b[..8].copy_from_slice(&header_sectors[0x000..0x008]);
-				b[8..].copy_from_slice(&header_sectors[0x100..0x104]);
-
-				b
-			};
+			let buf = raw_title(header_sectors);
+			DiscName::try_from(buf.as_slice()).unwrap_or_else(|e| {
+				actions.push(RepairAction::TitleTruncated(title_offset(e.position())));
+				// everything up to the bad character is known to be valid
+				DiscName::try_from(&buf[..e.position()]).unwrap_or_else(|_| DiscName::empty())
+			})
+		};
 
-			let name_len = buf.iter().take_while(|&&b| b > 32u8).count();
-			DiscName::try_from(&buf[..name_len]).map_err(|e| {
-				let str_pos = e.position();
-				// Decode index position back to byte offset
-				DFSError::InvalidDiscData(if str_pos >= 8 {
-					str_pos + 0xf8 // start of second sector; 0x008 -> 0x100
-				} else {
-					str_pos
-				})
-			})?
+		let boot_option = read_boot_option(header_sectors);
+
+		let cycle = BCD::from_hex(header_sectors[0x104]).unwrap_or_else(|_| {
+			actions.push(RepairAction::CycleReset);
+			BCD::C00
+		});
+
+		let num_catalogue_entries = match header_sectors[0x105] {
+			raw if raw & 7 == 0 => raw >> 3,
+			_ => {
+				let found = (0..MAX_FILES)
+					.take_while(|&i| CatalogueEntry::read_truncating(header_sectors, i).is_ok())
+					.count() as u8;
+				actions.push(RepairAction::FileCountRescanned(found));
+				found
+			},
 		};
 
-		// Disc sector count calculation. We don't check this against the
-		// length of `src`, as it's common to have this value declare all
-		// 40 or 80 tracks, for a disc image to then only include the ones
-		// containing file data. The source extent _is_ checked per-file.
-		{
-			const OFFSET : usize = 0x107;
-			let upper = ((header_sectors[OFFSET - 1] & 3) as u16) << 8;
-			let result = (header_sectors[OFFSET] as u16) | upper;
-			if result < 2 {
-				return Err(DFSError::InvalidDiscData(OFFSET));
+		// entries are kept packed, so a blank one means the count is too high;
+		// entries after it may still be good, though
+		let found = (0..num_catalogue_entries)
+			.filter(|&i| !CatalogueEntry::is_blank(header_sectors, i))
+			.count() as u8;
+		if found < num_catalogue_entries {
+			actions.push(RepairAction::FileCountLowered { declared: num_catalogue_entries, found });
+		}
+
+		let mut files = Vec::new();
+		let mut extents = ArrayVec::<std::ops::Range<u32>, { MAX_FILES as usize }>::new();
+		for i in 0..num_catalogue_entries {
+			if CatalogueEntry::is_blank(header_sectors, i) {
+				continue;
 			}
-			result
-		};
+			let mut entry = match CatalogueEntry::read_truncating(header_sectors, i) {
+				Ok((e, None)) => e,
+				Ok((e, Some(offset))) => {
+					actions.push(RepairAction::NameTruncated { index: i, offset });
+					e
+				},
+				Err(DFSError::InvalidDiscData(offset)) => {
+					actions.push(RepairAction::EntryDropped { index: i, offset });
+					continue;
+				},
+				Err(e) => return Err(e),
+			};
+			let offset2 = CatalogueEntry::offset2(i);
 
-		let boot_option = (header_sectors[0x106] >> 4) & 3;
-		let boot_option = BootOption::try_from(boot_option)?;
+			let data_start = entry.start_sector * 0x100;
+			if data_start < 0x200 || data_start > src.len() as u32 {
+				actions.push(RepairAction::EntryDropped { index: i, offset: offset2 + 7 });
+				continue;
+			}
+			if data_start + entry.file_len > src.len() as u32 {
+				entry.file_len = src.len() as u32 - data_start;
+				actions.push(RepairAction::ExtentClamped { index: i, len: entry.file_len });
+			}
 
-		let disc_cycle = {
-			const OFFSET : usize = 0x104;
-			BCD::from_hex(header_sectors[OFFSET])
-				.map_err(|_| DFSError::InvalidDiscData(OFFSET))?
-		};
+			let extent = entry.extent();
+			if extent.end > MAX_SECTORS as u32
+				|| extents.iter().any(|e| e.start < extent.end && extent.start < e.end) {
+				actions.push(RepairAction::EntryDropped { index: i, offset: offset2 + 7 });
+				continue;
+			}
 
-		let files = populate_files(src)?;
+			let data_end = data_start + entry.file_len;
+			let mut file = File::new(entry.name.clone(), entry.dir, entry.load_addr, entry.exec_addr,
+				entry.locked, Cow::Borrowed(&src[(data_start as usize)..(data_end as usize)]));
+			file.set_start_sector(entry.start_sector());
+			file.set_catalogue_index(i);
+			if files.contains(&file) {
+				actions.push(RepairAction::EntryDropped { index: i, offset: CatalogueEntry::offset1(i) });
+				continue;
+			}
+
+			extents.push(extent);
+			files.push(file);
+		}
+
+		let total_sectors = {
+			let declared = match read_total_sectors(header_sectors) {
+				n if n > MAX_SECTORS => {
+					actions.push(RepairAction::TotalSectorsLowered(MAX_SECTORS));
+					MAX_SECTORS
+				},
+				n => n,
+			};
+			let needed = extents.iter().map(|e| e.end as u16).fold(2, u16::max);
+			if declared < needed {
+				actions.push(RepairAction::TotalSectorsRaised(needed));
+			}
+			declared.max(needed)
+		};
 
 		let disc = Disc {
 			_data: PhantomData,
+			title_bytes: stored_title(header_sectors, &disc_name),
 			name: disc_name,
 			files,
 			boot_option,
-			cycle: disc_cycle,
+			cycle,
+			auto_increment_cycle: false,
+			strict_names: false,
+			total_sectors,
+			geometry: Some(Geometry::for_sectors(total_sectors)),
+			format_fill: None,
 		};
 
-		Ok(disc)
+		Ok((disc, actions))
+	}
+
+	/// Checks a disc image for damage, returning everything that
+	/// [`repair`](#method.repair) would have to change to read it. An image
+	/// that needs no changes gives an empty list.
+	///
+	/// Some damage, such as a sector count too small for the files on the
+	/// disc, is still accepted by [`from_bytes`](#method.from_bytes).
+	///
+	/// # Errors
+	/// Only [`DFSError::InputTooSmall`][DFSError], if `src` can't even hold
+	/// a catalogue.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn validate(src: &[u8]) -> Result<Vec<RepairAction>, DFSError> {
+		Disc::repair(src).map(|(_, actions)| actions)
 	}
 
-	pub fn files<'a>(&'a self) -> Files {
+	/// Checks if the catalogue holds [`MAX_FILES`](./constant.MAX_FILES.html)
+	/// files, so no more can be added.
+	pub fn is_full(&self) -> bool {
+		self.files.len() >= MAX_FILES as usize
+	}
+
+	/// Iterates over the files on the disc, in catalogue order. This order
+	/// doesn't depend on hashing, so it is the same on every run.
+	pub fn files(&self) -> Files<'_, 'd> {
 		Files(self.files.iter())
 	}
 
-	pub fn add_file(&mut self, file: File<'d>) -> Result<Option<File<'d>>, File<'d>> {
-		if self.files.len() >= MAX_FILES as usize {
-			return Err(file);
+	/// Adds a file to the end of the catalogue, or in place of any unlocked
+	/// file with the same name and directory. The replaced file is returned,
+	/// if there was one.
+	///
+	/// # Errors
+	/// The file is handed back, with the reason:
+	/// * [`DFSError::CatalogueFull`][DFSError]: the catalogue is already
+	///   full.
+	/// * [`DFSError::FileLocked`][DFSError]: it would replace a locked file.
+	/// * [`DFSError::InvalidValue`][DFSError]: its directory is reserved by
+	///   DFS (see [`AsciiPrintingChar::is_valid_dfs_dir`]), or, with
+	///   [strict names](#method.set_strict_names) on, its name is not
+	///   addressable.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	/// [`AsciiPrintingChar::is_valid_dfs_dir`]: ../support/struct.AsciiPrintingChar.html#method.is_valid_dfs_dir
+	pub fn add_file(&mut self, file: File<'d>) -> Result<Option<File<'d>>, (DFSError, File<'d>)> {
+		self.insert_file(file, false)
+	}
+
+	/// Like [`add_file`](#method.add_file), but replaces a file with the
+	/// same name even if it is locked.
+	pub fn force_add_file(&mut self, file: File<'d>) -> Result<Option<File<'d>>, (DFSError, File<'d>)> {
+		self.insert_file(file, true)
+	}
+
+	/// Adds each of `files` in turn, as [`add_file`](#method.add_file)
+	/// does, stopping at the first one that can't be added.
+	///
+	/// # Errors
+	/// The rejected file is handed back with the reason, as for `add_file`.
+	/// Files before it have already been added; files after it are left in
+	/// the iterator.
+	pub fn try_extend<I>(&mut self, files: I) -> Result<(), (DFSError, File<'d>)>
+	where I: IntoIterator<Item = File<'d>> {
+		for file in files {
+			self.add_file(file)?;
+		}
+		Ok(())
+	}
+
+	fn insert_file(&mut self, file: File<'d>, force: bool) -> Result<Option<File<'d>>, (DFSError, File<'d>)> {
+		if !file.dir().is_valid_dfs_dir() {
+			return Err((DFSError::InvalidValue, file));
+		}
+		if self.strict_names && !file.key().name.is_addressable() {
+			return Err((DFSError::InvalidValue, file));
 		}
+		match self.files.iter().position(|f| *f == file) {
+			Some(i) if self.files[i].is_locked() && !force =>
+				Err((DFSError::FileLocked(format!("{}.{}", file.dir(), file.name())), file)),
+			Some(i) => Ok(Some(std::mem::replace(&mut self.files[i], file))),
+			None if self.is_full() => Err((DFSError::CatalogueFull { max: MAX_FILES }, file)),
+			None => { self.files.push(file); Ok(None) },
+		}
+	}
+
+	/// Sorts the catalogue. [`to_image`](#method.to_image) writes files
+	/// contiguously from sector 2 in catalogue order, so sorting also moves
+	/// their data.
+	pub fn sort_files(&mut self, by: SortKey) {
+		self.files.sort_by(|a, b| by.compare(a, b));
+	}
+
+	/// The total length of every file's content, in bytes.
+	pub fn total_file_bytes(&self) -> u64 {
+		self.files.iter().map(|f| f.content().len() as u64).sum()
+	}
+
+	/// The number of sectors the files' data takes up. Each file starts on a
+	/// new sector, so this is usually more than
+	/// [`total_file_bytes`](#method.total_file_bytes) would suggest. The
+	/// catalogue's two sectors are not included.
+	pub fn sectors_used(&self) -> u32 {
+		self.files.iter().map(|f| f.content().len().sectors() as u32).sum()
+	}
+
+	/// The space left on the disc, in whole sectors' worth of bytes. This is
+	/// measured against the disc's [declared size](#method.total_sectors),
+	/// with the files laid out as [`to_image`](#method.to_image) would.
+	pub fn free_bytes(&self) -> u64 {
+		self.free_sectors() as u64 * SECTOR_SIZE as u64
+	}
+
+	/// Checks whether a new file of `content_len` bytes would fit, both in
+	/// the catalogue and in the [free space](#method.free_bytes). Replacing
+	/// an existing file frees its space first, which this doesn't account
+	/// for.
+	pub fn can_fit(&self, content_len: usize) -> bool {
+		!self.is_full()
+			&& content_len <= 0x3ffff
+			&& content_len.sectors() as u32 <= self.free_sectors()
+	}
+
+	fn free_sectors(&self) -> u32 {
+		(self.total_sectors as u32).saturating_sub(2 + self.sectors_used())
+	}
+
+	/// The files on the disc in the order given by `by`, leaving the
+	/// catalogue as it is.
+	pub fn files_sorted(&self, by: SortKey) -> Vec<&File<'d>> {
+		let mut files: Vec<_> = self.files.iter().collect();
+		files.sort_by(|a, b| by.compare(a, b));
+		files
+	}
 
-		Ok(self.files.replace(file))
+	/// Iterates over the files in directory `dir`, in catalogue order.
+	pub fn files_in_dir(&self, dir: AsciiPrintingChar) -> impl Iterator<Item = &File<'d>> + '_ {
+		self.files.iter().filter(move |f| f.dir() == dir)
+	}
+
+	/// Iterates over the files matching a wildcard pattern such as `B.*` or
+	/// `$.F#O`, in catalogue order. See [`glob`](./glob/index.html) for the
+	/// pattern syntax.
+	pub fn files_matching<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = &'a File<'d>> + 'a {
+		self.files.iter()
+			.filter(move |f| super::glob::matches(pattern, f.dir(), &f.key().name))
 	}
 
 	pub fn find_file(&self, file_name: &FileName, dir_name: AsciiPrintingChar) -> Option<&File<'d>> {
-		self.files.get(&super::file::Key::new(file_name.clone(), dir_name))
+		let key = super::file::Key::new(file_name.clone(), dir_name);
+		self.files.iter().find(|f| *f.key() == key)
+	}
+
+	/// The file that `SHIFT`+`BREAK` runs, loads or `*EXEC`s, depending on
+	/// the boot option: `$.!BOOT`, if there is one. As in DFS, case is
+	/// ignored.
+	pub fn boot_file(&self) -> Option<&File<'d>> {
+		self.files.iter().find(|f| f.dir() == AsciiPrintingChar::DOLLAR
+			&& f.name().as_str().eq_ignore_ascii_case("!BOOT"))
+	}
+
+	/// Checks that the boot option has something to boot: either it is
+	/// `None`, or there is a [`boot_file`](#method.boot_file). A `!BOOT` in
+	/// any other directory doesn't count, as DFS only looks in `$`.
+	pub fn boot_target_valid(&self) -> bool {
+		self.boot_option == BootOption::None || self.boot_file().is_some()
+	}
+
+	/// Finds a file to change in place, such as to set its addresses, lock
+	/// it or replace its content.
+	///
+	/// Renaming the file through this isn't checked: don't give it the name
+	/// of another file on the disc.
+	pub fn get_file_mut(&mut self, file_name: &FileName, dir_name: AsciiPrintingChar) -> Option<&mut File<'d>> {
+		let key = super::file::Key::new(file_name.clone(), dir_name);
+		self.files.iter_mut().find(|f| *f.key() == key)
 	}
 
 	pub fn remove_file(&mut self, file_name: &FileName, dir_name: AsciiPrintingChar) -> Option<File<'d>> {
-		self.files.take(&super::file::Key::new(file_name.clone(), dir_name))
+		let key = super::file::Key::new(file_name.clone(), dir_name);
+		let i = self.files.iter().position(|f| *f.key() == key)?;
+		Some(self.files.remove(i))
 	}
 
-	pub fn to_image(&self, target: &mut dyn io::Write) -> Result<u16, DFSError> {
-		use std::ops::Range;
-		// first, determine the ordering of files in the disc image
-		// then their sector spans, to ensure we have enough space
-
-		use std::num::NonZeroU16;
-		struct BuildData<'f, 'd> {
-			file: &'f File<'d>,
-			start_sector: NonZeroU16,
-			sector_count: u16,
-		}
-
-		let end_sector;
-		let file_indexes = {
-			let mut start_sector = NonZeroU16::new(2).unwrap();
-			let mut v = self.files.iter().map(|file| Ok(BuildData {
-				file,
-				start_sector, // to be assigned after sort
-				sector_count: match file.content().len() {
-					yes if yes <= 0x3ffff => yes.sectors() as u16,
-					no => return Err(DFSError::InputTooLarge(no))
+	/// Removes a file, as DFS's `*DELETE` would: a locked file is left alone.
+	/// Returns the removed file, or `None` if there was no such file.
+	///
+	/// # Errors
+	/// [`DFSError::FileLocked`][DFSError] if the file is locked.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn remove_file_checked(&mut self, file_name: &FileName, dir_name: AsciiPrintingChar)
+	-> Result<Option<File<'d>>, DFSError> {
+		match self.find_file(file_name, dir_name) {
+			Some(f) if f.is_locked() => Err(DFSError::FileLocked(format!("{}.{}", dir_name, file_name))),
+			_ => Ok(self.remove_file(file_name, dir_name)),
+		}
+	}
+
+	/// Adds every file in a host directory (but not its subdirectories) to
+	/// the DFS directory `dir`, as unlocked data files. Returns how many
+	/// files were added.
+	///
+	/// Each file's name is taken from its host name without the extension:
+	/// uppercased, with accents stripped, characters DFS can't address
+	/// dropped, and cut to 7 characters. Names that then clash are dealt
+	/// with according to `collisions`. Files are added in host name order;
+	/// any that won't fit in the catalogue or on the disc are skipped, as are
+	/// files left with no usable name.
+	///
+	/// # Errors
+	/// * [`DFSError::DuplicateFileName`][DFSError]: with
+	///   [`CollisionPolicy::Error`], two host files end up with the same DFS
+	///   name, or a file's name is already on the disc. Nothing is added.
+	/// * [`DFSError::InvalidValue`][DFSError]: `dir` is reserved by DFS
+	///   (see [`AsciiPrintingChar::is_valid_dfs_dir`]). Nothing is added.
+	/// * [`DFSError::Io`][DFSError]: the directory or one of its files
+	///   couldn't be read.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	/// [`CollisionPolicy::Error`]: ./enum.CollisionPolicy.html#variant.Error
+	/// [`AsciiPrintingChar::is_valid_dfs_dir`]: ../support/struct.AsciiPrintingChar.html#method.is_valid_dfs_dir
+	pub fn add_dir(&mut self, path: &std::path::Path, dir: AsciiPrintingChar, collisions: CollisionPolicy)
+	-> Result<usize, DFSError> {
+		if !dir.is_valid_dfs_dir() {
+			return Err(DFSError::InvalidValue);
+		}
+
+		let mut host_files = Vec::new();
+		for entry in std::fs::read_dir(path)? {
+			let entry = entry?;
+			if entry.file_type()?.is_file() {
+				host_files.push(entry.path());
+			}
+		}
+		host_files.sort();
+
+		let mut names: Vec<(FileName, std::path::PathBuf)> = Vec::with_capacity(host_files.len());
+		for host_path in host_files {
+			let name = match host_file_name(&host_path) {
+				Some(name) => name,
+				None => continue,
+			};
+			let is_taken = |name: &FileName| self.find_file(name, dir).is_some()
+				|| names.iter().any(|(n, _)| n == name);
+			let name = match (is_taken(&name), collisions) {
+				(false, _) => name,
+				(true, CollisionPolicy::Error) =>
+					return Err(DFSError::DuplicateFileName(format!("{}.{}", dir, name))),
+				(true, CollisionPolicy::Skip) => continue,
+				(true, CollisionPolicy::NumericSuffix) => match (1..).map(|n| numbered_name(&name, n))
+					.take_while(Option::is_some)
+					.flatten()
+					.find(|n| !is_taken(n)) {
+					Some(name) => name,
+					None => continue,
+				},
+			};
+			names.push((name, host_path));
+		}
+
+		let mut added = 0;
+		for (name, host_path) in names {
+			let content = std::fs::read(&host_path)?;
+			match self.add_file(File::data(name.clone(), dir, Cow::Owned(content))) {
+				Ok(_) => {},
+				Err((DFSError::CatalogueFull { .. }, _)) => continue,
+				Err((e, _)) => return Err(e),
+			}
+			// only running out of space is a reason to skip a file
+			match self.catalogue() {
+				Ok(_) => added += 1,
+				Err(DFSError::InputTooLarge(_)) | Err(DFSError::FileTooLarge { .. }) => {
+					self.remove_file(&name, dir);
+				},
+				Err(e) => {
+					self.remove_file(&name, dir);
+					return Err(e);
 				},
-			})).collect::<Result<ArrayVec<_, { MAX_FILES as usize }>, _>>()?;
-			v.sort_unstable_by_key(|b: &BuildData| b.file.key().clone());
-
-			for data in &mut v {
-				data.start_sector = start_sector;
-				start_sector = match
-				// must not overflow when added to existing sector ptr
-				start_sector.get().checked_add(data.sector_count)
-				// and must also be non-zero (guaranteed)
-				.and_then(NonZeroU16::new) {
-					Some(s) => s,
-					None => return Err(DFSError::InputTooLarge(0x1_0000)),
-				};
 			}
-			end_sector = start_sector.get();
-			v
-		};
+		}
 
-		if end_sector > MAX_SECTORS {
+		Ok(added)
+	}
+
+	/// The catalogue that [`to_image`](#method.to_image) would write for
+	/// this disc, with the files laid out in order from sector 2.
+	///
+	/// # Errors
+	/// * [`DFSError::FileTooLarge`][DFSError]: a file is longer than DFS can
+	///   record (`&3FFFF` bytes).
+	/// * [`DFSError::InputTooLarge(usize)`][DFSError]: the files together
+	///   don't fit in the disc's fixed [geometry](#method.geometry), or on
+	///   an 80-track disc if it has none. The attached `usize` is the
+	///   sector they would have ended at.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub fn catalogue(&self) -> Result<Catalogue, DFSError> {
+		self.layout().map(|(catalogue, _, _)| catalogue)
+	}
+
+	/// Assigns each file its place on disc, returning the catalogue, the
+	/// files in the same order as its entries, and where each one goes.
+	fn layout(&self) -> Result<(Catalogue, ArrayVec<&File<'d>, { MAX_FILES as usize }>, Layout), DFSError> {
+		let files = self.files.iter().collect::<ArrayVec<_, { MAX_FILES as usize }>>();
+
+		let mut catalogue = Catalogue::new(self.name.clone(), self.cycle,
+			self.boot_option, self.total_sectors);
+		catalogue.title_bytes = self.title_bytes;
+		let mut placements = Vec::with_capacity(files.len());
+		let mut start_sector = 2u16;
+		for file in &files {
+			let sector_count = match file.content().len() {
+				yes if yes <= 0x3ffff => yes.sectors() as u16,
+				no => return Err(DFSError::FileTooLarge {
+					key: format!("{}.{}", file.dir(), file.name()),
+					len: no,
+				}),
+			};
+			catalogue.push(CatalogueEntry::for_file(file, start_sector));
+			placements.push(FilePlacement { file: FileId::of(file), start_sector, sector_count });
+			// must not overflow when added to existing sector ptr
+			start_sector = start_sector.checked_add(sector_count)
+				.ok_or(DFSError::InputTooLarge(0x1_0000))?;
+		}
+
+		let end_sector = start_sector;
+		if end_sector > self.geometry.map_or(MAX_SECTORS, Geometry::sectors) {
 			return Err(DFSError::InputTooLarge(end_sector as usize));
 		}
 
-		let mut sectors = 2u16;
-		let mut buf = [0u8; 256];
-		let mut write_buf = |buf: &mut [u8; 256], sectors: &mut u16|
-		-> Result<(), DFSError> {
-			target.write_all(&buf[..])?;
-			*buf = [0u8; 256];
-			// we only call `write_buf` for first two sectors; it *will not* wrap
-			*sectors = sectors.wrapping_add(1);
-			Ok(())
-		};
+		// keep the declared disc size, unless the files have outgrown it
+		catalogue.total_sectors = self.total_sectors.max(end_sector);
 
-		fn buf_for_entry(idx: usize) -> Range<usize> {
-			(idx+1)*8 .. (idx+2)*8
-		}
-
-		// sector 0: start of disc name, file names
-		buf[..8].copy_space_padded(self.name().up_to(8));
-
-		for (i, data) in file_indexes.iter().enumerate() {
-			// transform i into offset
-			let dst = &mut buf[buf_for_entry(i)];
-
-			// copy file name
-			dst[..7].copy_space_padded(data.file.key().name
-				.as_ascii_str().as_bytes());
-			dst[7] = data.file.key().dir.as_byte();
-		}
-
-		write_buf(&mut buf, &mut sectors)?;
-
-		// sector 1: FS metadata mop-up, file entries
-		buf[..4].copy_space_padded(self.name().from_up_to(8..12));
-		buf[4] = self.cycle().into_u8();
-		buf[5] = (self.files.len() as u8).wrapping_mul(8); // won't wrap
-		buf[6] = /* b4,5 = boot option  */ (self.boot_option as u8) << 4
-		       | /* b0,1 = sectors b8,9 */ ((sectors & 0x300) >> 8) as u8;
-		buf[7] = (end_sector & 255) as u8;
-
-		for (i, data) in file_indexes.iter().enumerate() {
-			let load  = data.file.load_addr().to_le_bytes();
-			let exec  = data.file.exec_addr().to_le_bytes();
-			let len   = (data.file.content().len() as u32).to_le_bytes();
-			let start = data.start_sector.get().to_le_bytes();
-			buf[buf_for_entry(i)].copy_from_slice(&[
-				// load low
-				load[0], load[1],
-				// exec low
-				exec[0], exec[1],
-				// len low
-				len[0], len[1],
-				// highs
-				((exec[2] & 3) << 6) |
-				((len [2] & 3) << 4) |
-				((load[2] & 3) << 2) |
-				((start[1] & 3) << 0),
-				// sector low
-				start[0]
-			][..]);
-		};
-		write_buf(&mut buf, &mut sectors)?;
+		Ok((catalogue, files, Layout { files: placements, end_sector }))
+	}
+
+	/// Works out where [`to_image`](#method.to_image) would put each file,
+	/// without writing anything.
+	///
+	/// # Errors
+	/// As for [`catalogue`](#method.catalogue).
+	pub fn plan_layout(&self) -> Result<Layout, DFSError> {
+		self.layout().map(|(_, _, layout)| layout)
+	}
+
+	/// The length of the smallest image that holds every file, which is
+	/// what [`to_image`](#method.to_image) writes for an unformatted disc.
+	///
+	/// # Errors
+	/// As for [`catalogue`](#method.catalogue).
+	pub fn min_image_len(&self) -> Result<u64, DFSError> {
+		self.plan_layout().map(|layout| layout.end_sector as u64 * SECTOR_SIZE as u64)
+	}
+
+	/// Records on each file where [`to_image`](#method.to_image) would put
+	/// its data, as reported by [`File::start_sector`]. Returns the first
+	/// sector after the last file.
+	///
+	/// # Errors
+	/// As for [`catalogue`](#method.catalogue). No file is changed on error.
+	///
+	/// [`File::start_sector`]: ./struct.File.html#method.start_sector
+	pub fn place_files(&mut self) -> Result<u16, DFSError> {
+		let layout = self.plan_layout()?;
+		for (file, placement) in self.files.iter_mut().zip(&layout.files) {
+			file.set_start_sector(placement.start_sector);
+		}
+		Ok(layout.end_sector)
+	}
+
+	/// Maps out what each sector of the disc holds, for every sector the
+	/// catalogue declares.
+	///
+	/// Files are shown where they [start](./struct.File.html#method.start_sector);
+	/// files that haven't been placed on disc yet are left out, so call
+	/// [`place_files`](#method.place_files) first to include them. Sectors
+	/// past the declared size are not shown, even if a file reaches them.
+	pub fn sector_map(&self) -> Vec<SectorState> {
+		let mut map = vec![SectorState::Free; self.total_sectors as usize];
+		for state in map.iter_mut().take(2) {
+			*state = SectorState::Catalogue;
+		}
+
+		for file in &self.files {
+			let start = match file.start_sector() {
+				Some(s) => s as usize,
+				None => continue,
+			};
+			let end = (start + file.content().len().sectors()).min(map.len());
+			for state in map.iter_mut().take(end).skip(start) {
+				*state = SectorState::File(FileId::of(file));
+			}
+		}
+
+		map
+	}
+
+	/// The offset just past the last byte of file data, going by where each
+	/// file [starts](./struct.File.html#method.start_sector), or the end of
+	/// the catalogue if no file has been placed. Anything in the source
+	/// image from here on isn't used by any file.
+	///
+	/// Files that haven't been placed on disc are left out, as for
+	/// [`sector_map`](#method.sector_map).
+	pub fn max_referenced_offset(&self) -> usize {
+		self.files.iter()
+			.filter_map(|f| f.start_sector().map(|s| s as usize * SECTOR_SIZE + f.content().len()))
+			.fold(SECTOR_SIZE * 2, usize::max)
+	}
+
+	/// Looks for data left behind by deleted files in `original`, the image
+	/// the disc was read from. DFS only removes a file's catalogue entry, so
+	/// its data stays put until something else is written over it.
+	///
+	/// Every run of sectors that no file uses, per
+	/// [`sector_map`](#method.sector_map), and that holds something other
+	/// than `&00` or `&E5` (as left by formatting) is returned, in disc
+	/// order. Sectors past the declared size or the end of `original`
+	/// are not looked at.
+	pub fn scan_deleted(&self, original: &[u8]) -> Vec<RecoveredRegion> {
+		let mut regions: Vec<RecoveredRegion> = Vec::new();
+		let sectors = original.chunks(SECTOR_SIZE).enumerate();
+		for ((sector, data), state) in sectors.zip(self.sector_map()) {
+			let blank = data.iter().all(|&b| b == 0) || data.iter().all(|&b| b == FORMAT_FILL);
+			if state != SectorState::Free || blank {
+				continue;
+			}
+			match regions.last_mut() {
+				Some(r) if r.start_sector + r.sector_count == sector as u16 => r.sector_count += 1,
+				_ => regions.push(RecoveredRegion { start_sector: sector as u16, sector_count: 1 }),
+			}
+		}
+		regions
+	}
+
+	/// Writes the disc image into a new `Vec`, as [`to_image`](#method.to_image)
+	/// would.
+	pub fn to_vec(&self) -> Result<Vec<u8>, DFSError> {
+		let mut image = Vec::new();
+		self.to_image(&mut image)?;
+		Ok(image)
+	}
+
+	/// Writes the disc out as an image, with its files laid out in
+	/// catalogue order from sector 2. Returns the first sector after the
+	/// last file.
+	///
+	/// Files are never re-sorted on the way out: the catalogue lists them in
+	/// the order [`files`](#method.files) gives, which for a disc read with
+	/// [`from_bytes`](#method.from_bytes) is the order of the image's own
+	/// catalogue. Reading an image this wrote gives back an equal disc, and
+	/// writing that out again gives the same bytes. An image from a real DFS,
+	/// which lists its files by descending start sector, keeps its catalogue
+	/// order but has its data moved.
+	///
+	/// The disc's own settings decide how: see
+	/// [`write_options`](#method.write_options).
+	///
+	/// # Errors
+	/// As for [`catalogue`](#method.catalogue), or any error from `target`.
+	pub fn to_image(&self, target: &mut dyn io::Write) -> Result<u16, DFSError> {
+		self.to_image_with(target, self.write_options())
+	}
+
+	/// The options [`to_image`](#method.to_image) writes with. For a disc
+	/// from [`new`](#method.new) these are the defaults; a
+	/// [formatted](#method.new_formatted) disc fills and pads with `0xE5`,
+	/// and [`set_auto_increment_cycle`](#method.set_auto_increment_cycle)
+	/// turns on `increment_cycle`.
+	pub fn write_options(&self) -> WriteOptions {
+		WriteOptions {
+			fill_byte: self.format_fill.unwrap_or(0),
+			pad_to_geometry: self.format_fill.is_some(),
+			increment_cycle: self.auto_increment_cycle,
+		}
+	}
+
+	/// Like [`to_image`](#method.to_image), but with the given options in
+	/// place of the disc's own settings.
+	pub fn to_image_with(&self, target: &mut dyn io::Write, opts: WriteOptions) -> Result<u16, DFSError> {
+		let (mut catalogue, files, layout) = self.layout()?;
+		if opts.increment_cycle {
+			catalogue.cycle = self.next_cycle();
+		}
+
+		target.write_all(&catalogue.to_bytes())?;
+
+		let buf = [opts.fill_byte; SECTOR_SIZE];
+
+		for file in &files {
+			let content = file.content();
+			target.write_all(content)?;
+			match content.len() & 0xff {
+				0 => {},
+				n => target.write_all(&buf[n..])?
+			};
+		}
+
+		if opts.pad_to_geometry {
+			for _ in layout.end_sector..catalogue.total_sectors() {
+				target.write_all(&buf)?;
+			}
+		}
+
+		Ok(layout.end_sector)
+	}
+}
+
+/// How [`Disc::to_image_with`] writes an image. The default is what
+/// [`Disc::to_image`] uses for a new disc.
+///
+/// [`Disc::to_image_with`]: ./struct.Disc.html#method.to_image_with
+/// [`Disc::to_image`]: ./struct.Disc.html#method.to_image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteOptions {
+	/// The byte to fill the rest of each file's last sector with, and any
+	/// padding.
+	pub fill_byte: u8,
+	/// Whether to write out every sector the disc declares, rather than
+	/// stopping after the last file.
+	pub pad_to_geometry: bool,
+	/// Whether to write the next cycle count rather than the current one.
+	pub increment_cycle: bool,
+}
+
+/// Two discs are equal if they have the same title, boot option, cycle count
+/// and files, in any order.
+///
+/// Unlike [`File`](./struct.File.html) equality, which only compares names,
+/// files here must match in their addresses, lock state and content too.
+/// Where each file lies on disc, the declared disc size, and settings such
+/// as [strict names](#method.set_strict_names) are not compared.
+impl<'a, 'b> PartialEq<Disc<'b>> for Disc<'a> {
+	fn eq(&self, other: &Disc<'b>) -> bool {
+		self.name == other.name
+			&& self.boot_option == other.boot_option
+			&& self.cycle == other.cycle
+			&& self.files.len() == other.files.len()
+			&& self.files.iter().all(|f| other.files.iter().any(|g| f.deep_eq(g)))
+	}
+}
+
+impl<'d> TryFrom<&'d [u8]> for Disc<'d> {
+	type Error = DFSError;
+
+	/// Same as [`Disc::from_bytes`](#method.from_bytes).
+	fn try_from(src: &'d [u8]) -> Result<Disc<'d>, DFSError> { Disc::from_bytes(src) }
+}
+
+impl<'d> TryFrom<&Disc<'d>> for Vec<u8> {
+	type Error = DFSError;
+
+	fn try_from(disc: &Disc<'d>) -> Result<Vec<u8>, DFSError> { disc.to_vec() }
+}
+
+/// Where [`Disc::to_image`] would put each file, as worked out by
+/// [`Disc::plan_layout`].
+///
+/// [`Disc::to_image`]: ./struct.Disc.html#method.to_image
+/// [`Disc::plan_layout`]: ./struct.Disc.html#method.plan_layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+	/// Where each file goes, in catalogue order.
+	pub files: Vec<FilePlacement>,
+	/// The first sector after the last file.
+	pub end_sector: u16,
+}
+
+/// Where one file goes on disc, as part of a [`Layout`](./struct.Layout.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePlacement {
+	pub file: FileId,
+	pub start_sector: u16,
+	/// How many sectors the file's data takes up, including the unused end
+	/// of its last sector.
+	pub sector_count: u16,
+}
+
+/// What a sector holds, as given by [`Disc::sector_map`].
+///
+/// [`Disc::sector_map`]: ./struct.Disc.html#method.sector_map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectorState {
+	/// One of the two catalogue sectors.
+	Catalogue,
+	/// Data belonging to this file.
+	File(FileId),
+	/// Not used by any file.
+	Free,
+}
+
+/// Sectors that may hold a deleted file, as found by [`Disc::scan_deleted`].
+///
+/// [`Disc::scan_deleted`]: ./struct.Disc.html#method.scan_deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveredRegion {
+	pub start_sector: u16,
+	pub sector_count: u16,
+}
+
+impl RecoveredRegion {
+	/// The region's data in `image`, cut short if the image ends first.
+	pub fn data<'a>(&self, image: &'a [u8]) -> &'a [u8] {
+		let start = (self.start_sector as usize * SECTOR_SIZE).min(image.len());
+		let end = (start + self.sector_count as usize * SECTOR_SIZE).min(image.len());
+		&image[start..end]
+	}
+}
+
+/// A change made by [`Disc::repair`] to recover a damaged disc image.
+///
+/// [`Disc::repair`]: ./struct.Disc.html#method.repair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairAction {
+	/// The disc title had an invalid character at this offset, and was cut
+	/// short there.
+	TitleTruncated(usize),
+	/// The cycle count wasn't valid BCD, and was reset to 0.
+	CycleReset,
+	/// The file count byte wasn't a multiple of 8. This many valid entries
+	/// were found by scanning the catalogue instead.
+	FileCountRescanned(u8),
+	/// The file count byte declared more entries than there were: only
+	/// `found` of them weren't blank. The blank ones were skipped.
+	FileCountLowered { declared: u8, found: u8 },
+	/// A file name had a byte that can't be part of a name at `offset`,
+	/// with more than padding after it, and was cut short there.
+	NameTruncated { index: u8, offset: usize },
+	/// A catalogue entry was dropped, because of the bad data at `offset`.
+	EntryDropped { index: u8, offset: usize },
+	/// A file ran past the end of the image, and was cut short to `len`.
+	ExtentClamped { index: u8, len: u32 },
+	/// The declared sector count was more than an 80-track disc has, and
+	/// was lowered to this.
+	TotalSectorsLowered(u16),
+	/// The declared sector count was too small to hold the files, and was
+	/// raised to this.
+	TotalSectorsRaised(u16),
+}
 
-		for data in file_indexes {
-			let content = data.file.content();
-			target.write_all(content)?;
-			match content.len() & 0xff {
-				0 => {},
-				n =>
-					// write_buf is empty
-					target.write_all(&buf[n..])?
-			};
+impl RepairAction {
+	/// The offset into the disc image of the data that needed repair.
+	pub fn offset(&self) -> usize {
+		match *self {
+			Self::TitleTruncated(offset) => offset,
+			Self::CycleReset => 0x104,
+			Self::FileCountRescanned(_) => 0x105,
+			Self::FileCountLowered { .. } => 0x105,
+			Self::NameTruncated { offset, .. } => offset,
+			Self::EntryDropped { offset, .. } => offset,
+			Self::ExtentClamped { index, .. } => CatalogueEntry::offset2(index) + 6,
+			Self::TotalSectorsLowered(_) | Self::TotalSectorsRaised(_) => 0x107,
 		}
+	}
+}
 
-		Ok(end_sector)
+impl std::fmt::Display for RepairAction {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match *self {
+			Self::TitleTruncated(_) => f.write_str("title has an invalid character"),
+			Self::CycleReset => f.write_str("cycle count is not valid BCD"),
+			Self::FileCountRescanned(found) =>
+				write!(f, "file count is not a multiple of 8; {} entries look valid", found),
+			Self::FileCountLowered { declared, found } =>
+				write!(f, "file count is {}, but only {} entries aren't blank", declared, found),
+			Self::NameTruncated { index, .. } =>
+				write!(f, "file {} has garbage in its name", index),
+			Self::EntryDropped { index, .. } => write!(f, "catalogue entry {} is invalid", index),
+			Self::ExtentClamped { index, len } =>
+				write!(f, "file {} runs past the end of the image; only {} bytes are present", index, len),
+			Self::TotalSectorsLowered(max) =>
+				write!(f, "sector count is more than a disc can have; lowered to {}", max),
+			Self::TotalSectorsRaised(needed) =>
+				write!(f, "sector count is too small; the files need {}", needed),
+		}
 	}
 }
 
-pub struct Files<'a, 'd>(::std::collections::hash_set::Iter<'a, File<'d>>);
+impl<'d> Default for Disc<'d> {
+	fn default() -> Self { Self::new() }
+}
+
+pub struct Files<'a, 'd>(::std::slice::Iter<'a, File<'d>>);
 
 impl<'a, 'd> Iterator for Files<'a, 'd> {
 	type Item = &'a File<'d>;
@@ -366,62 +1404,91 @@ impl<'a, 'd> Iterator for Files<'a, 'd> {
 	}
 }
 
-fn populate_files(src: &[u8])
--> Result<HashSet<File>, DFSError> {
-	let num_catalogue_entries = {
-		const OFFSET : usize = 0x105;
-		let raw = src[OFFSET];
-		if (raw & 0x07) != 0 { return Err(DFSError::InvalidDiscData(OFFSET)); }
-
-		raw >> 3
-	};
-
-	let mut files = HashSet::new();
-	files.reserve(num_catalogue_entries as usize);
-
-	for i in 0..num_catalogue_entries {
-		// First half: filename, directory name, locked bit
-		let offset1 = ((i*8) as usize) + 0x008;
-		// Second half: various addresses
-		let offset2 = ((i*8) as usize) + 0x108;
-
-		// Set dir, locked
-		let (dir, locked) = {
-			let offset = offset1 + 7;
-			let raw = src[offset];
+/// Iterates over the files on a disc, as [`Disc::files`] does.
+///
+/// [`Disc::files`]: ./struct.Disc.html#method.files
+///
+/// # Examples
+///
+/// ```rust
+/// use std::borrow::Cow;
+/// use dfsdisc::dfs;
+/// use dfsdisc::support::AsciiPrintingChar;
+///
+/// let mut disc = dfs::Disc::new();
+/// let name = dfs::FileName::try_from(&b"HELLO"[..]).unwrap();
+/// disc.add_file(dfs::File::new(name, AsciiPrintingChar::DOLLAR, 0, 0, false,
+///     Cow::Borrowed(&b"hello"[..]))).unwrap();
+///
+/// for file in &disc {
+///     assert_eq!("HELLO", file.name());
+/// }
+/// ```
+impl<'a, 'd> IntoIterator for &'a Disc<'d> {
+	type Item = &'a File<'d>;
+	type IntoIter = Files<'a, 'd>;
 
-			let dir = AsciiPrintingChar::from(raw & 0x7f)
-				.map_err(|_| DFSError::InvalidDiscData(offset))?;
+	fn into_iter(self) -> Self::IntoIter { self.files() }
+}
 
-			(dir, raw > 0x7f)
-		};
+/// Adds files as [`Disc::add_file`] does.
+///
+/// # Panics
+/// If a file can't be added: the catalogue is full, the file would replace
+/// a locked one, or its directory is reserved. Use [`Disc::try_extend`] to
+/// handle these instead.
+///
+/// [`Disc::add_file`]: ./struct.Disc.html#method.add_file
+/// [`Disc::try_extend`]: ./struct.Disc.html#method.try_extend
+impl<'d> Extend<File<'d>> for Disc<'d> {
+	fn extend<I: IntoIterator<Item = File<'d>>>(&mut self, files: I) {
+		if let Err((e, file)) = self.try_extend(files) {
+			panic!("couldn't add {}.{}: {}", file.dir(), file.name(), e);
+		}
+	}
+}
 
-		let name = {
-			let name_buf = &src[offset1 .. (offset1 + 7)];
-			let name_len = name_buf.iter().take_while(|&&b| b > b' ').count();
-			FileName::try_from(&name_buf[..name_len]).map_err(|e| {
-				let str_pos = e.position();
-				DFSError::InvalidDiscData(offset1 + str_pos)
-			})?
-		};
+/// Makes a DFS file name from a host file's name, as described for
+/// [`Disc::add_dir`](./struct.Disc.html#method.add_dir).
+fn host_file_name(path: &std::path::Path) -> Option<FileName> {
+	let stem = path.file_stem()?.to_string_lossy();
+	let (stem, _) = AsciiName::<255>::from_transliterated(&stem);
+	let name: Vec<AsciiPrintingChar> = stem.iter()
+		.copied()
+		.filter(|&c| !super::file::is_reserved_in_name(c))
+		.take(7)
+		.collect();
+
+	match name.is_empty() {
+		true => None,
+		false => FileName::try_from(&name[..]).ok().map(|n| n.to_ascii_uppercase()),
+	}
+}
 
-		let busy_byte = src[offset2 + 6] as u32;
+/// `name` with its end replaced by `n`, or `None` if `n` has more than 7
+/// digits.
+fn numbered_name(name: &FileName, n: u32) -> Option<FileName> {
+	let digits = n.to_string();
+	let keep = name.len().min(7usize.checked_sub(digits.len())?);
+	let mut numbered = name.as_bytes()[..keep].to_vec();
+	numbered.extend_from_slice(digits.as_bytes());
+	FileName::try_from(&numbered[..]).ok()
+}
 
-		// Load/Exec
-		let load_addr = (u16_from_le(&src[offset2 .. offset2 + 2]) as u32)
-			| ((busy_byte << 14) & 0x30000);
-		let exec_addr = (u16_from_le(&src[offset2 + 2 .. offset2 + 4]) as u32)
-			| ((busy_byte << 10) & 0x30000);
+/// Reads every file in `catalogue` from `src`, checking that each file's
+/// data lies within `src` and doesn't overlap any other.
+fn populate_files<'d>(src: &'d [u8], catalogue: &Catalogue)
+-> Result<Vec<File<'d>>, DFSError> {
+	let mut files = Vec::with_capacity(catalogue.entries().len());
+	// sector spans of files seen so far, to catch overlaps
+	let mut extents = ArrayVec::<std::ops::Range<u32>, { MAX_FILES as usize }>::new();
 
-		// File length and start sector
-		let file_len = (u16_from_le(&src[offset2 + 4 .. offset2 + 6]) as u32)
-			| ((busy_byte << 12) & 0x30000);
-		let start_sector = (src[offset2 + 7] as u32)
-			| ((busy_byte << 8) & 0x300);
+	for (i, entry) in catalogue.entries().iter().enumerate() {
+		let offset2 = CatalogueEntry::offset2(i as u8);
 
 		// Validate data offsets
-		let data_start = start_sector * 0x100;
-		let data_end = data_start + file_len;
+		let data_start = entry.start_sector * 0x100;
+		let data_end = data_start + entry.file_len;
 		if data_start < 0x200 {
 			return Err(DFSError::InvalidDiscData(offset2 + 7));
 		}
@@ -429,17 +1496,25 @@ fn populate_files(src: &[u8])
 			return Err(DFSError::InvalidDiscData(offset2 + 6));
 		}
 
+		let extent = entry.extent();
+		if extents.iter().any(|e| e.start < extent.end && extent.start < e.end) {
+			return Err(DFSError::InvalidDiscData(offset2 + 7));
+		}
+		extents.push(extent); // at most 31 entries, so this fits
+
 		let file_contents = &src[(data_start as usize)..(data_end as usize)];
-		let file = File::new(name, dir, load_addr, exec_addr, locked,
-			Cow::Borrowed(file_contents));
+		let mut file = File::new(entry.name.clone(), entry.dir, entry.load_addr, entry.exec_addr,
+			entry.locked, Cow::Borrowed(file_contents));
+		file.set_start_sector(entry.start_sector());
+		file.set_catalogue_index(i as u8);
 
 		if files.contains(&file) {
 			return Err(DFSError::DuplicateFileName(
-				format!("{}.{}", dir, file.name())
+				format!("{}.{}", entry.dir, file.name())
 				));
 		}
 
-		files.insert(file);
+		files.push(file);
 	}
 
 	Ok(files)
@@ -477,6 +1552,7 @@ mod test {
 
 		// Check cycle count
 		assert_eq!(BCD::from_hex(0x11).unwrap(), target.cycle());
+		assert_eq!("11", target.cycle_display());
 
 		for f in target.files() {
 			println!("Found file {:?}", f);
@@ -516,6 +1592,46 @@ mod test {
 		assert_eq!(test_name, target.name().as_bytes());
 	}
 
+	#[test]
+	fn disc_name_with_spaces() {
+		let buf = disc_buf_with_name(b"AB CD       ");
+		let disc = dfs::Disc::from_bytes(&buf).unwrap();
+		assert_eq!("AB CD", disc.name());
+		assert_eq!(b"AB CD       ", &disc.raw_title_bytes());
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(b"AB CD   ", &image[0x000..0x008]);
+		assert_eq!(b"    ", &image[0x100..0x104]);
+		assert_eq!("AB CD", dfs::Disc::from_bytes(&image).unwrap().name());
+
+		// a title ends at its first control code
+		let buf = disc_buf_with_name(b"AB\0CD   ");
+		assert_eq!("AB", dfs::Disc::from_bytes(&buf).unwrap().name());
+	}
+
+	#[test]
+	fn disc_title_keeps_padding() {
+		let buf = disc_buf_with_name(b"AB CD\0\0\0\0\0\0\0");
+		let mut disc = dfs::Disc::from_bytes(&buf).unwrap();
+		assert_eq!("AB CD", disc.name());
+		assert_eq!(b"AB CD\0\0\0\0\0\0\0", &disc.raw_title_bytes());
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(&buf[0x000..0x008], &image[0x000..0x008]);
+		assert_eq!(&buf[0x100..0x104], &image[0x100..0x104]);
+
+		// a new name is padded with spaces
+		disc.set_name(AsciiPrintingStr::try_from_str("NEW").unwrap()).unwrap();
+		assert_eq!(b"NEW         ", &disc.raw_title_bytes());
+
+		// left-over data after the title isn't written back
+		let buf = disc_buf_with_name(b"DISC\0XY\0\0\0\0\0");
+		let disc = dfs::Disc::from_bytes(&buf).unwrap();
+		assert_eq!(b"DISC        ", &disc.raw_title_bytes());
+	}
+
 	#[test]
 	fn disc_name_top_bits_set() {
 		let disc_name = ::ascii::AsciiStr::from_ascii(b"DiscName").unwrap();
@@ -567,6 +1683,27 @@ mod test {
 		}
 	}
 
+	#[test]
+	fn boot_option_round_trip() {
+		use dfs::BootOption;
+
+		for &boot_option in &[BootOption::None, BootOption::Load, BootOption::Run, BootOption::Exec] {
+			let mut disc = dfs::Disc::new();
+			*disc.boot_option_mut() = boot_option;
+			// set both high sector count bits, so they can't be confused
+			disc.set_total_sectors(0x31f).unwrap();
+
+			let mut image = Vec::new();
+			disc.to_image(&mut image).unwrap();
+			assert_eq!((boot_option as u8) << 4 | 3, image[0x106]);
+			assert_eq!(0x1f, image[0x107]);
+
+			let disc = dfs::Disc::from_bytes(&image).unwrap();
+			assert_eq!(boot_option, disc.boot_option());
+			assert_eq!(0x31f, disc.total_sectors());
+		}
+	}
+
 	#[test]
 	fn invalid_sector_count() {
 		let case = |n| {
@@ -584,6 +1721,1161 @@ mod test {
 		case(1);
 	}
 
+	#[test]
+	fn total_sectors_round_trip() {
+		let mut buf = disc_buf_with_name(b"DiscName");
+		buf[0x106] = 0x01;
+		buf[0x107] = 0x90;
+		let buf = buf;
+
+		let disc = dfs::Disc::from_bytes(&buf).unwrap();
+		assert_eq!(400, disc.total_sectors());
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!([0x01, 0x90], image[0x106..0x108]);
+
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(400, disc.total_sectors());
+	}
+
+	#[test]
+	fn set_total_sectors() {
+		let mut disc = dfs::Disc::new();
+		assert_eq!(Err(dfs::DFSError::InvalidValue), disc.set_total_sectors(1));
+		assert_eq!(Err(dfs::DFSError::InvalidValue), disc.set_total_sectors(801));
+		assert_eq!(Ok(()), disc.set_total_sectors(800));
+		assert_eq!(800, disc.total_sectors());
+	}
+
+	#[test]
+	fn add_file_replaces_and_keeps_lock() {
+		use std::borrow::Cow;
+
+		let name = dfs::FileName::try_from(&b"LOCKED"[..]).unwrap();
+		let new_file = |locked, content: &'static [u8]| dfs::File::new(name.clone(),
+			AsciiPrintingChar::DOLLAR, 0x1900, 0x8023, locked, Cow::Borrowed(content));
+
+		let mut disc = dfs::Disc::new();
+		assert!(matches!(disc.add_file(new_file(false, b"old")), Ok(None)));
+		assert!(matches!(disc.add_file(new_file(true, b"new")), Ok(Some(_))));
+		assert_eq!(1, disc.files().count());
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(b'$' | 0x80, image[0x00f]);
+
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		let file = disc.find_file(&name, AsciiPrintingChar::DOLLAR).unwrap();
+		assert!(file.is_locked());
+		assert_eq!(b"new", file.content());
+	}
+
+	#[test]
+	fn boot_target() {
+		use std::borrow::Cow;
+
+		let boot = dfs::FileName::try_from(&b"!BOOT"[..]).unwrap();
+		let mut disc = dfs::Disc::new();
+		assert!(disc.boot_target_valid());
+
+		*disc.boot_option_mut() = dfs::BootOption::Run;
+		assert!(disc.boot_file().is_none());
+		assert!(!disc.boot_target_valid());
+
+		disc.add_file(dfs::File::data(boot.clone(), AsciiPrintingChar::from(b'A').unwrap(),
+			Cow::Borrowed(&[]))).unwrap();
+		assert!(!disc.boot_target_valid());
+
+		disc.add_file(dfs::File::data(boot, AsciiPrintingChar::DOLLAR, Cow::Borrowed(&[]))).unwrap();
+		assert_eq!(Some(AsciiPrintingChar::DOLLAR), disc.boot_file().map(|f| f.dir()));
+		assert!(disc.boot_target_valid());
+	}
+
+	#[test]
+	fn get_file_mut() {
+		use std::borrow::Cow;
+
+		let name = dfs::FileName::try_from(&b"CODE"[..]).unwrap();
+		let mut disc = dfs::Disc::new();
+		disc.add_file(dfs::File::code(name.clone(), AsciiPrintingChar::DOLLAR, 0x1900, 0x1900,
+			Cow::Borrowed(b"\x60"))).unwrap();
+
+		let file = disc.get_file_mut(&name, AsciiPrintingChar::DOLLAR).unwrap();
+		file.set_load_addr(0x3000);
+		file.set_exec_addr(0x3001);
+		file.set_content(Cow::Borrowed(b"\xea\x60"));
+		file.lock();
+
+		let file = disc.find_file(&name, AsciiPrintingChar::DOLLAR).unwrap();
+		assert_eq!((0x3000, 0x3001), (file.load_addr(), file.exec_addr()));
+		assert_eq!(b"\xea\x60", file.content());
+		assert!(file.is_locked());
+
+		assert!(disc.get_file_mut(&name, AsciiPrintingChar::from(b'A').unwrap()).is_none());
+	}
+
+	#[test]
+	fn locked_files_are_guarded() {
+		use std::borrow::Cow;
+
+		let name = dfs::FileName::try_from(&b"LOCKED"[..]).unwrap();
+		let new_file = |content: &'static [u8]| dfs::File::new(name.clone(),
+			AsciiPrintingChar::DOLLAR, 0x1900, 0x8023, true, Cow::Borrowed(content));
+
+		let mut disc = dfs::Disc::new();
+		assert!(matches!(disc.add_file(new_file(b"old")), Ok(None)));
+
+		assert_eq!(Err(dfs::DFSError::FileLocked("$.LOCKED".into())),
+			disc.remove_file_checked(&name, AsciiPrintingChar::DOLLAR).map(|_| ()));
+		assert!(matches!(disc.add_file(new_file(b"new")), Err((dfs::DFSError::FileLocked(_), _))));
+		assert_eq!(b"old", disc.find_file(&name, AsciiPrintingChar::DOLLAR).unwrap().content());
+
+		assert!(matches!(disc.force_add_file(new_file(b"new")), Ok(Some(_))));
+		assert_eq!(b"new", disc.find_file(&name, AsciiPrintingChar::DOLLAR).unwrap().content());
+
+		let other = dfs::FileName::try_from(&b"OTHER"[..]).unwrap();
+		assert!(matches!(disc.remove_file_checked(&other, AsciiPrintingChar::DOLLAR), Ok(None)));
+		assert!(disc.remove_file(&name, AsciiPrintingChar::DOLLAR).is_some());
+	}
+
+	/// Makes a fresh host directory for a test, holding the given files.
+	fn host_dir(test: &str, files: &[(&str, &[u8])]) -> std::path::PathBuf {
+		let path = std::env::temp_dir()
+			.join(format!("dfsdisc-{}-{}", test, std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		std::fs::create_dir_all(path.join("subdir")).unwrap();
+		for (name, content) in files {
+			std::fs::write(path.join(name), content).unwrap();
+		}
+		path
+	}
+
+	fn file_names(disc: &dfs::Disc) -> Vec<String> {
+		disc.files().map(|f| format!("{}.{}", f.dir(), f.name())).collect()
+	}
+
+	#[test]
+	fn add_dir() {
+		let big = vec![0u8; 200 * 1024];
+		let huge = vec![0u8; 0x40000];
+		let path = host_dir("add-dir", &[("readme.txt", b"hello"), ("Café", b"menu"),
+			("big.bin", &big), ("huge.bin", &huge)]);
+
+		let mut disc = dfs::Disc::new();
+		let b = AsciiPrintingChar::from(b'B').unwrap();
+		let added = disc.add_dir(&path, b, dfs::CollisionPolicy::Error);
+		let reserved = dfs::Disc::new().add_dir(&path, AsciiPrintingChar::from(b'#').unwrap(),
+			dfs::CollisionPolicy::Error);
+		std::fs::remove_dir_all(&path).unwrap();
+
+		assert_eq!(Ok(2), added);
+		assert_eq!(["B.CAFE", "B.README"], &file_names(&disc)[..]);
+		assert_eq!(b"hello", disc.find_file(&dfs::FileName::try_from(&b"README"[..]).unwrap(), b)
+			.unwrap().content());
+
+		// a reserved directory is an error, not just nothing added
+		assert_eq!(Err(dfs::DFSError::InvalidValue), reserved);
+	}
+
+	#[test]
+	fn add_dir_collisions() {
+		use dfs::CollisionPolicy;
+
+		let path = host_dir("add-dir-collisions",
+			&[("LONGNAME1", b"1"), ("LONGNAME2", b"2"), ("LONGNA1", b"3")]);
+		let add = |collisions| {
+			let mut disc = dfs::Disc::new();
+			disc.add_dir(&path, AsciiPrintingChar::DOLLAR, collisions).map(|n| (n, file_names(&disc)))
+		};
+		let error = add(CollisionPolicy::Error);
+		let suffix = add(CollisionPolicy::NumericSuffix);
+		let skip = add(CollisionPolicy::Skip);
+		std::fs::remove_dir_all(&path).unwrap();
+
+		// LONGNA1 sorts first, so takes the first suffixed name
+		assert_eq!(Err(dfs::DFSError::DuplicateFileName("$.LONGNAM".into())), error);
+		assert_eq!(Ok((3, vec!["$.LONGNA1".into(), "$.LONGNAM".into(), "$.LONGNA2".into()])), suffix);
+		assert_eq!(Ok((2, vec!["$.LONGNA1".into(), "$.LONGNAM".into()])), skip);
+
+		assert_eq!(Ok(CollisionPolicy::NumericSuffix), "numeric-suffix".parse());
+	}
+
+	#[test]
+	fn auto_increment_cycle() {
+		let mut disc = dfs::Disc::new();
+		*disc.cycle_mut() = BCD::try_new(19).unwrap();
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(0x19, image[0x104]);
+
+		disc.set_auto_increment_cycle(true);
+		image.clear();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(0x20, image[0x104]);
+		assert_eq!(BCD::try_new(19).unwrap(), disc.cycle());
+
+		*disc.cycle_mut() = BCD::C99;
+		image.clear();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(0x00, image[0x104]);
+	}
+
+	#[test]
+	fn add_file_reserved_dir() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		for &dir in b".*" {
+			let file = dfs::File::new(dfs::FileName::try_from(&b"FOO"[..]).unwrap(),
+				AsciiPrintingChar::from(dir).unwrap(), 0, 0, false, Cow::Borrowed(&[]));
+			assert!(disc.add_file(file).is_err());
+		}
+		assert_eq!(0, disc.files().count());
+	}
+
+	#[test]
+	fn strict_names() {
+		use std::borrow::Cow;
+
+		let name = |n: &[u8]| dfs::FileName::try_from(n).unwrap();
+		assert!(name(b"FOO").is_addressable());
+		assert!(!name(b"FO*O").is_addressable());
+
+		let new_file = |n| dfs::File::new(name(n), AsciiPrintingChar::DOLLAR,
+			0, 0, false, Cow::Borrowed(&[]));
+
+		let mut disc = dfs::Disc::new();
+		assert!(disc.add_file(new_file(b"FO*O")).is_ok());
+		disc.set_strict_names(true);
+		assert!(disc.add_file(new_file(b"FO#O")).is_err());
+		assert!(disc.add_file(new_file(b"FOO")).is_ok());
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert!(dfs::Disc::from_bytes(&image).is_ok());
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x00a)),
+			dfs::Disc::from_bytes_strict(&image).map(|_| ()));
+	}
+
+	#[test]
+	fn raw_attributes_round_trip() {
+		use dfs::testing::*;
+
+		let files = [FileSpec { dir: b'$', name: b"HIGH", load_addr: 0x3_1900, exec_addr: 0x2_8023,
+			locked: false, content: &[0; 0x10] }];
+		let image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		assert_eq!(0b10_00_11_00, image[0x10e]);
+
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		let file = disc.files().next().unwrap();
+		assert_eq!(image[0x10e], file.raw_attributes());
+		assert_eq!(image, disc.to_vec().unwrap());
+	}
+
+	#[test]
+	fn strict_padding() {
+		use dfs::testing::*;
+
+		let files = [FileSpec::data(b'$', b"FOO", b"")];
+		let clean = make_image(&DiscSpec { name: b"DISC", files: &files, ..Default::default() });
+		assert!(dfs::Disc::from_bytes_strict(&clean).is_ok());
+
+		// zero padding is as good as spaces
+		let mut padded = clean.clone();
+		padded[0x004..0x008].fill(0);
+		padded[0x100..0x104].fill(0);
+		padded[0x00b..0x00f].fill(0);
+		let disc = dfs::Disc::from_bytes_strict(&padded).unwrap();
+		assert_eq!("DISC", disc.name());
+
+		let mut title_garbage = padded.clone();
+		title_garbage[0x101] = b'X';
+		assert_eq!("DISC", dfs::Disc::from_bytes(&title_garbage).unwrap().name());
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x101)),
+			dfs::Disc::from_bytes_strict(&title_garbage).map(|_| ()));
+
+		let mut name_garbage = padded;
+		name_garbage[0x00d] = b'Z';
+		assert!(dfs::Disc::from_bytes(&name_garbage).is_ok());
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x00d)),
+			dfs::Disc::from_bytes_strict(&name_garbage).map(|_| ()));
+	}
+
+	#[test]
+	fn overlapping_files() {
+		use dfs::testing::*;
+
+		let files = [
+			FileSpec::data(b'$', b"ONE", &[1; 300]),
+			FileSpec::data(b'$', b"TWO", &[2; 10]),
+		];
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		assert!(dfs::Disc::from_bytes(&image).is_ok());
+
+		// point the second file at sector 2 too
+		image[0x117] = 2;
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x117)),
+			dfs::Disc::from_bytes(&image).map(|_| ()));
+
+		// and at the second sector of the first file
+		image[0x117] = 3;
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x117)),
+			dfs::Disc::from_bytes(&image).map(|_| ()));
+	}
+
+	#[test]
+	fn repair() {
+		use dfs::testing::*;
+		use dfs::RepairAction;
+
+		let files = [
+			FileSpec::data(b'$', b"ONE", &[1; 10]),
+			FileSpec::data(b'$', b"TWO", &[2; 10]),
+			FileSpec::data(b'$', b"THREE", &[3; 300]),
+		];
+		let mut image = make_image(&DiscSpec { name: b"BROKEN", files: &files, ..Default::default() });
+		image[0x104] = 0xaa; // bad cycle
+		image[0x105] = 0x19; // bad file count
+		image[0x117] = 0; // TWO starts in the catalogue
+		image.truncate(0x500); // THREE loses its last 44 bytes
+
+		assert!(dfs::Disc::from_bytes(&image).is_err());
+		let (disc, actions) = dfs::Disc::repair(&image).unwrap();
+		assert_eq!(vec![
+			RepairAction::CycleReset,
+			RepairAction::FileCountRescanned(3),
+			RepairAction::EntryDropped { index: 1, offset: 0x117 },
+			RepairAction::ExtentClamped { index: 2, len: 256 },
+		], actions);
+
+		assert_eq!("BROKEN", disc.name());
+		assert_eq!(2, disc.files().count());
+		let three = disc.find_file(&dfs::FileName::try_from(&b"THREE"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR).unwrap();
+		assert_eq!(256, three.content().len());
+
+		// a valid image needs no repairs
+		let image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		assert_eq!(Vec::<RepairAction>::new(), dfs::Disc::repair(&image).unwrap().1);
+	}
+
+	#[test]
+	fn validate() {
+		use dfs::testing::*;
+
+		let files = [FileSpec::data(b'$', b"ONE", &[1; 300])];
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		assert_eq!(Ok(vec![]), dfs::Disc::validate(&image));
+
+		image[0x104] = 0x1a;
+		image.truncate(0x300);
+		let issues = dfs::Disc::validate(&image).unwrap();
+		assert_eq!(vec![0x104, 0x10e], issues.iter().map(|i| i.offset()).collect::<Vec<_>>());
+		assert_eq!("file 0 runs past the end of the image; only 256 bytes are present",
+			issues[1].to_string());
+
+		assert_eq!(Err(dfs::DFSError::InputTooSmall(0x200)), dfs::Disc::validate(&image[..0x100]));
+	}
+
+	#[test]
+	fn name_truncated() {
+		use dfs::testing::*;
+		use dfs::RepairAction;
+
+		let files = [
+			FileSpec::data(b'$', b"FOOBAR", b"x"),
+			FileSpec::data(b'$', b"WORDS", b"y"),
+		];
+		let names = |disc: &dfs::Disc| disc.files().map(|f| f.name().to_string()).collect::<Vec<_>>();
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		image[0x008..0x00f].copy_from_slice(b"FO\0OBAR");
+
+		// a NUL ends a name anyway; only strict reading minds what follows
+		assert_eq!(vec!["FO", "WORDS"], names(&dfs::Disc::from_bytes(&image).unwrap()));
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x00b)),
+			dfs::Disc::from_bytes_strict(&image).map(|_| ()));
+
+		image[0x012] = 0xff; // in the middle of WORDS
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x012)), dfs::Disc::from_bytes(&image).map(|_| ()));
+
+		let (disc, actions) = dfs::Disc::repair(&image).unwrap();
+		assert_eq!(vec![
+			RepairAction::NameTruncated { index: 0, offset: 0x00a },
+			RepairAction::NameTruncated { index: 1, offset: 0x012 },
+		], actions);
+		assert_eq!(vec!["FO", "WO"], names(&disc));
+	}
+
+	#[test]
+	fn blank_entry_within_count() {
+		use dfs::testing::*;
+
+		let files = [
+			FileSpec::data(b'$', b"ONE", &[1; 10]),
+			FileSpec::data(b'$', b"TWO", &[2; 10]),
+			FileSpec::data(b'$', b"THREE", &[3; 10]),
+		];
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		image[0x010..0x018].fill(0);
+		image[0x110..0x118].fill(0);
+
+		assert!(dfs::Disc::from_bytes(&image).is_err());
+		let (disc, actions) = dfs::Disc::repair(&image).unwrap();
+		let lowered = dfs::RepairAction::FileCountLowered { declared: 3, found: 2 };
+		assert_eq!(vec![lowered], actions);
+		assert_eq!(0x105, lowered.offset());
+		assert_eq!("file count is 3, but only 2 entries aren't blank", lowered.to_string());
+		// entries after the blank one are still read
+		assert_eq!(vec!["ONE", "THREE"], disc.files().map(|f| f.name().to_string()).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn full_catalogue_count_in_short_buffer() {
+		// 31 files declared, but nothing past the catalogue
+		let mut buf = disc_buf_with_name(b"DiscName");
+		buf[0x105] = 31 << 3;
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x00f)),
+			dfs::Disc::from_bytes(&buf).map(|_| ()));
+
+		// all 31 entries plausible, all pointing past the end of the buffer
+		for i in 0..31 {
+			let entry = 0x008 + i * 8;
+			buf[entry..entry + 8].copy_from_slice(b"FILE   $");
+			buf[entry + 4] = b'A' + i as u8;
+			let entry = 0x108 + i * 8;
+			buf[entry..entry + 8].copy_from_slice(&[0, 0, 0, 0, 0xff, 0xff, 0x33, 0xff]);
+		}
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x10e)),
+			dfs::Disc::from_bytes(&buf).map(|_| ()));
+		assert!(dfs::Catalogue::from_bytes(&buf[..0x1ff]).is_err());
+	}
+
+	#[test]
+	fn repair_empty_file_past_end() {
+		use dfs::testing::*;
+
+		let files = [FileSpec::data(b'$', b"EMPTY", b"")];
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		image[0x10f] = 0x20;
+
+		assert!(dfs::Disc::from_bytes(&image).is_err());
+		let (disc, actions) = dfs::Disc::repair(&image).unwrap();
+		assert_eq!(0, disc.files().count());
+		assert_eq!(vec![dfs::RepairAction::EntryDropped { index: 0, offset: 0x10f }], actions);
+	}
+
+	/// A cheap stand-in for the fuzz target: neither parser may panic on
+	/// random or randomly-damaged input.
+	#[test]
+	fn parsers_never_panic() {
+		use dfs::testing::*;
+
+		let mut state = 0x2545_f491_4f6c_dd1du64;
+		let mut next = move || {
+			// xorshift64
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			state
+		};
+
+		let files = [
+			FileSpec { dir: b'$', name: b"ONE", load_addr: 0x1900, exec_addr: 0x8023,
+				locked: true, content: &[1; 700] },
+			FileSpec::data(b'A', b"TWO", &[]),
+			FileSpec { dir: b'B', name: b"THREE", load_addr: 0x3ffff, exec_addr: 0x3ffff,
+				locked: false, content: &[3; 5] },
+		];
+		let valid = make_image(&DiscSpec { name: b"FUZZ", files: &files, ..Default::default() });
+
+		for _ in 0..2000 {
+			let mut image = valid.clone();
+			for _ in 0..(next() % 8) {
+				let pos = (next() as usize) % image.len();
+				image[pos] = next() as u8;
+			}
+			image.truncate((next() as usize) % (valid.len() + 1));
+			let _ = dfs::Disc::from_bytes(&image);
+			let _ = dfs::Disc::repair(&image);
+
+			let random: Vec<u8> = (0..(next() % 1024)).map(|_| next() as u8).collect();
+			let _ = dfs::Disc::from_bytes(&random);
+			let _ = dfs::Disc::repair(&random);
+		}
+	}
+
+	#[test]
+	fn new_formatted() {
+		for &geometry in &[dfs::Geometry::Tracks40, dfs::Geometry::Tracks80] {
+			let disc = dfs::Disc::new_formatted(geometry);
+			assert_eq!(geometry, disc.geometry());
+
+			let mut image = Vec::new();
+			disc.to_image(&mut image).unwrap();
+			assert_eq!(geometry.sectors() as usize * dfs::SECTOR_SIZE, image.len());
+			assert!(image[0x200..].iter().all(|&b| b == 0xe5));
+
+			let disc = dfs::Disc::from_bytes(&image).unwrap();
+			assert_eq!(0, disc.files().count());
+			assert_eq!(geometry.sectors(), disc.total_sectors());
+			assert_eq!(geometry, disc.geometry());
+		}
+	}
+
+	#[test]
+	fn track_count() {
+		use std::borrow::Cow;
+		use dfs::testing::*;
+
+		for &(sectors, tracks) in &[(2, 40), (399, 40), (400, 40), (401, 80), (800, 80)] {
+			let image = make_image(&DiscSpec { total_sectors: sectors, ..Default::default() });
+			assert_eq!(tracks, dfs::Disc::from_bytes(&image).unwrap().track_count());
+		}
+
+		// a 40-track disc can't grow past 400 sectors
+		let image = make_image(&DiscSpec::default());
+		let mut disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(40, disc.track_count());
+		disc.add_file(dfs::File::data(dfs::FileName::try_from(&b"BIG"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; 399 * 256]))).unwrap();
+		assert_eq!(Err(dfs::DFSError::InputTooLarge(401)), disc.to_vec());
+
+		disc.set_geometry(dfs::Geometry::Tracks80).unwrap();
+		assert_eq!(80, disc.track_count());
+		assert_eq!(401, dfs::Disc::from_bytes(&disc.to_vec().unwrap()).unwrap().total_sectors());
+
+		disc.set_total_sectors(600).unwrap();
+		assert_eq!(Err(dfs::DFSError::InvalidValue), disc.set_geometry(dfs::Geometry::Tracks40));
+		let mut disc = dfs::Disc::new();
+		disc.set_total_sectors(500).unwrap();
+		assert_eq!(80, disc.track_count());
+
+		// a new disc has no fixed geometry, so its files decide
+		let mut disc = dfs::Disc::new();
+		assert_eq!(40, disc.track_count());
+		disc.add_file(dfs::File::data(dfs::FileName::try_from(&b"BIG"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; 450 * 256]))).unwrap();
+		assert_eq!(80, disc.track_count());
+		assert_eq!(452, dfs::Disc::from_bytes(&disc.to_vec().unwrap()).unwrap().total_sectors());
+		disc.set_geometry(dfs::Geometry::Tracks80).unwrap();
+		disc.add_file(dfs::File::data(dfs::FileName::try_from(&b"MORE"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; 349 * 256]))).unwrap();
+		assert_eq!(Err(dfs::DFSError::InputTooLarge(801)), disc.to_vec());
+	}
+
+	#[test]
+	fn too_many_sectors() {
+		use dfs::testing::*;
+		use dfs::RepairAction;
+
+		let mut image = make_image(&DiscSpec { total_sectors: 800, ..Default::default() });
+		assert_eq!(800, dfs::Disc::from_bytes(&image).unwrap().total_sectors());
+
+		image[0x107] = 0xff; // 1023 sectors
+		assert_eq!(Err(dfs::DFSError::InvalidDiscData(0x107)), dfs::Disc::from_bytes(&image).map(|_| ()));
+		let (disc, actions) = dfs::Disc::repair(&image).unwrap();
+		assert_eq!(vec![RepairAction::TotalSectorsLowered(800)], actions);
+		assert_eq!(800, disc.total_sectors());
+		assert_eq!(80, disc.track_count());
+	}
+
+	#[test]
+	fn sort_files() {
+		use dfs::testing::*;
+
+		let files = [
+			FileSpec { load_addr: 0x3000, ..FileSpec::data(b'B', b"ALPHA", b"a") },
+			FileSpec { load_addr: 0x1000, ..FileSpec::data(b'$', b"GAMMA", b"g") },
+			FileSpec { load_addr: 0x2000, ..FileSpec::data(b'A', b"BETA", b"b") },
+		];
+		let image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		let mut disc = dfs::Disc::from_bytes(&image).unwrap();
+
+		let order = |disc: &dfs::Disc| -> Vec<String> {
+			let mut image = Vec::new();
+			disc.to_image(&mut image).unwrap();
+			let catalogue = dfs::Catalogue::from_bytes(&image).unwrap();
+			catalogue.entries().iter().map(|e| {
+				// each file's data should follow it around
+				let data = image[e.start_sector() as usize * dfs::SECTOR_SIZE];
+				assert_eq!(e.name().as_str().as_bytes()[0].to_ascii_lowercase(), data);
+				format!("{}.{}", e.dir(), e.name())
+			}).collect()
+		};
+
+		// catalogue order is kept until sorted
+		assert_eq!(["B.ALPHA", "$.GAMMA", "A.BETA"], &order(&disc)[..]);
+		disc.sort_files(dfs::SortKey::Directory);
+		assert_eq!(["$.GAMMA", "A.BETA", "B.ALPHA"], &order(&disc)[..]);
+		disc.sort_files(dfs::SortKey::Name);
+		assert_eq!(["B.ALPHA", "A.BETA", "$.GAMMA"], &order(&disc)[..]);
+		disc.sort_files(dfs::SortKey::LoadAddr);
+		assert_eq!(["$.GAMMA", "A.BETA", "B.ALPHA"], &order(&disc)[..]);
+
+		assert_eq!(Ok(dfs::SortKey::LoadAddr), "Load-Addr".parse());
+	}
+
+	#[test]
+	fn file_totals() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		assert_eq!((0, 0), (disc.total_file_bytes(), disc.sectors_used()));
+
+		for (name, len) in [(&b"ONE"[..], 0x100), (b"TWO", 0x101), (b"EMPTY", 0)] {
+			disc.add_file(dfs::File::data(dfs::FileName::try_from(name).unwrap(),
+				AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; len]))).unwrap();
+		}
+		assert_eq!((0x201, 3), (disc.total_file_bytes(), disc.sectors_used()));
+	}
+
+	#[test]
+	fn can_fit() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new_formatted(dfs::Geometry::Tracks40);
+		assert_eq!(398 * 256, disc.free_bytes());
+
+		disc.add_file(dfs::File::data(dfs::FileName::try_from(&b"BULK"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; 396 * 256 + 1]))).unwrap();
+		assert_eq!(256, disc.free_bytes());
+		assert!(disc.can_fit(0));
+		assert!(disc.can_fit(256));
+		assert!(!disc.can_fit(257));
+
+		for i in 1..dfs::MAX_FILES {
+			let name = dfs::FileName::try_from(format!("E{}", i).as_bytes()).unwrap();
+			disc.add_file(dfs::File::data(name, AsciiPrintingChar::DOLLAR, Cow::Borrowed(&[])))
+				.unwrap();
+		}
+		assert_eq!(256, disc.free_bytes());
+		assert!(!disc.can_fit(0));
+
+		let extra = dfs::File::data(dfs::FileName::try_from(&b"EXTRA"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Borrowed(&[]));
+		match disc.add_file(extra) {
+			Err((e, file)) => {
+				assert_eq!(dfs::DFSError::CatalogueFull { max: 31 }, e);
+				assert_eq!("EXTRA", file.name());
+			},
+			Ok(_) => panic!("32nd file was added"),
+		}
+	}
+
+	#[test]
+	fn extend() {
+		use std::borrow::Cow;
+
+		let file = |i: u8| dfs::File::data(dfs::FileName::try_from(format!("F{}", i).as_bytes()).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Borrowed(&[]));
+		let mut disc = dfs::Disc::new();
+		disc.extend((0..30).map(file));
+		assert_eq!(30, disc.files().count());
+
+		let mut rest = (30..35).map(file);
+		match disc.try_extend(&mut rest) {
+			Err((e, rejected)) => {
+				assert_eq!(dfs::DFSError::CatalogueFull { max: 31 }, e);
+				assert_eq!("F31", rejected.name());
+			},
+			Ok(()) => panic!("catalogue overflowed"),
+		}
+		assert!(disc.is_full());
+		assert_eq!(vec!["F32", "F33", "F34"], rest.map(|f| f.name().to_string()).collect::<Vec<_>>());
+
+		let overflow = std::panic::catch_unwind(move || disc.extend(Some(file(99))));
+		assert!(overflow.is_err());
+	}
+
+	#[test]
+	fn files_sorted_and_in_dir() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		for (dir, name) in [(b'B', &b"ZED"[..]), (b'$', b"ONE"), (b'B', b"ALPHA")] {
+			disc.add_file(dfs::File::data(dfs::FileName::try_from(name).unwrap(),
+				AsciiPrintingChar::from(dir).unwrap(), Cow::Borrowed(b""))).unwrap();
+		}
+
+		let names = |files: Vec<&dfs::File>| files.iter()
+			.map(|f| format!("{}.{}", f.dir(), f.name())).collect::<Vec<_>>();
+		assert_eq!(["$.ONE", "B.ALPHA", "B.ZED"], &names(disc.files_sorted(dfs::SortKey::Directory))[..]);
+		assert_eq!(["B.ZED", "B.ALPHA"],
+			&names(disc.files_in_dir(AsciiPrintingChar::from(b'B').unwrap()).collect())[..]);
+		// the catalogue is untouched
+		assert_eq!("ZED", disc.files().next().unwrap().name());
+	}
+
+	#[test]
+	fn boot_option_from_opt4_arg() {
+		use dfs::BootOption;
+		assert_eq!(Ok(BootOption::Run), BootOption::from_opt4_arg("2"));
+		assert_eq!(Ok(BootOption::Run), BootOption::from_opt4_arg("run"));
+		assert_eq!(Ok(BootOption::Run), BootOption::from_opt4_arg("RUN"));
+		assert_eq!(Err(dfs::DFSError::InvalidValue), BootOption::from_opt4_arg("4"));
+	}
+
+	#[test]
+	fn set_boot_option_str() {
+		let mut disc = dfs::Disc::new();
+		for (s, expected) in [("run", dfs::BootOption::Run), ("EXEC", dfs::BootOption::Exec),
+			("1", dfs::BootOption::Load), ("0", dfs::BootOption::None)] {
+			disc.set_boot_option_str(s).unwrap();
+			assert_eq!(expected, disc.boot_option());
+		}
+
+		assert_eq!(Err(dfs::DFSError::InvalidValue), disc.set_boot_option_str("4"));
+		assert_eq!(Err(dfs::DFSError::InvalidValue), disc.set_boot_option_str("chain"));
+		assert_eq!(dfs::BootOption::None, disc.boot_option());
+	}
+
+	#[test]
+	fn with_files() {
+		use std::borrow::Cow;
+
+		let file = |name: &str, dir: u8| dfs::File::data(
+			dfs::FileName::try_from(name.as_bytes()).unwrap(),
+			AsciiPrintingChar::from(dir).unwrap(), Cow::Borrowed(&b"data"[..]));
+		let title = dfs::DiscName::try_from(&b"BUILT"[..]).unwrap();
+
+		let disc = dfs::Disc::with_files(title.clone(), dfs::BootOption::Run, BCD::C00,
+			vec![file("ONE", b'$'), file("TWO", b'A'), file("ONE", b'A')]).unwrap();
+		assert_eq!("BUILT", disc.name());
+		assert_eq!(dfs::BootOption::Run, disc.boot_option());
+		assert_eq!(vec!["$.ONE", "A.TWO", "A.ONE"], file_names(&disc));
+
+		assert_eq!(Err(dfs::DFSError::DuplicateFileName("A.ONE".into())),
+			dfs::Disc::with_files(title.clone(), dfs::BootOption::None, BCD::C00,
+				vec![file("ONE", b'A'), file("ONE", b'A')]));
+
+		let names: Vec<String> = (0..32).map(|i| format!("F{}", i)).collect();
+		assert_eq!(Err(dfs::DFSError::CatalogueFull { max: 31 }),
+			dfs::Disc::with_files(title, dfs::BootOption::None, BCD::C00,
+				names.iter().map(|n| file(n, b'$'))));
+	}
+
+	#[test]
+	fn catalogue_order_is_kept() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		for name in [&b"ZED"[..], b"MID", b"ALPHA"] {
+			let name = dfs::FileName::try_from(name).unwrap();
+			disc.add_file(dfs::File::data(name, AsciiPrintingChar::DOLLAR,
+				Cow::Borrowed(b"data"))).unwrap();
+		}
+		// replacing a file keeps its place
+		let mid = dfs::FileName::try_from(&b"MID"[..]).unwrap();
+		disc.add_file(dfs::File::data(mid, AsciiPrintingChar::DOLLAR,
+			Cow::Borrowed(b"new"))).unwrap().unwrap();
+
+		let names: Vec<_> = disc.files().map(|f| f.name().as_str()).collect();
+		assert_eq!(["ZED", "MID", "ALPHA"], &names[..]);
+
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		let reread = dfs::Disc::from_bytes(&image).unwrap();
+		let names: Vec<_> = reread.files().map(|f| f.name().as_str()).collect();
+		assert_eq!(["ZED", "MID", "ALPHA"], &names[..]);
+
+		let mut image2 = Vec::new();
+		reread.to_image(&mut image2).unwrap();
+		assert_eq!(image, image2);
+
+		let indices: Vec<_> = reread.files().map(|f| f.catalogue_index()).collect();
+		assert_eq!([Some(0), Some(1), Some(2)], &indices[..]);
+
+		// sorting can be undone
+		let mut sorted = reread.clone();
+		sorted.sort_files(dfs::SortKey::Name);
+		assert_eq!("ALPHA", sorted.files().next().unwrap().name());
+		assert_eq!(Some(2), sorted.files().next().unwrap().catalogue_index());
+		sorted.sort_files(dfs::SortKey::CatalogueIndex);
+		assert_eq!(image, sorted.to_vec().unwrap());
+	}
+
+	#[test]
+	fn start_sectors() {
+		use std::borrow::Cow;
+		use dfs::testing::*;
+
+		let files = [
+			FileSpec::data(b'$', b"BIG", &[0; 0x201]),
+			FileSpec::data(b'$', b"SMALL", b"x"),
+		];
+		let image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		let mut disc = dfs::Disc::from_bytes(&image).unwrap();
+		let sectors: Vec<_> = disc.files().map(|f| f.start_sector()).collect();
+		assert_eq!([Some(2), Some(5)], &sectors[..]);
+
+		let name = dfs::FileName::try_from(&b"NEW"[..]).unwrap();
+		disc.remove_file(&dfs::FileName::try_from(&b"BIG"[..]).unwrap(), AsciiPrintingChar::DOLLAR);
+		disc.add_file(dfs::File::data(name, AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"y")))
+			.unwrap();
+		let sectors: Vec<_> = disc.files().map(|f| f.start_sector()).collect();
+		assert_eq!([Some(5), None], &sectors[..]);
+
+		assert_eq!(Ok(4), disc.place_files());
+		let sectors: Vec<_> = disc.files().map(|f| f.start_sector()).collect();
+		assert_eq!([Some(2), Some(3)], &sectors[..]);
+
+		let names = |disc: &dfs::Disc| -> Vec<String> {
+			disc.files().map(|f| f.name().to_string()).collect()
+		};
+		disc.sort_files(dfs::SortKey::Name);
+		assert_eq!(["NEW", "SMALL"], &names(&disc)[..]);
+		disc.sort_files(dfs::SortKey::StartSector);
+		assert_eq!(["SMALL", "NEW"], &names(&disc)[..]);
+		assert_eq!(Ok(dfs::SortKey::StartSector), "Start-Sector".parse());
+	}
+
+	#[test]
+	fn zero_length_files() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		for (name, load, content) in [(&b"FIRST"[..], 0x1100, &b""[..]), (b"DATA", 0x1200, b"x"),
+			(b"MIDDLE", 0x1300, b""), (b"LAST", 0x3_1400, b"")] {
+			disc.add_file(dfs::File::new(dfs::FileName::try_from(name).unwrap(),
+				AsciiPrintingChar::DOLLAR, load, load + 1, false, Cow::Borrowed(content))).unwrap();
+		}
+
+		let layout = disc.plan_layout().unwrap();
+		let placements: Vec<_> = layout.files.iter().map(|p| (p.start_sector, p.sector_count)).collect();
+		assert_eq!(vec![(2, 0), (2, 1), (3, 0), (3, 0)], placements);
+		assert_eq!(1, disc.sectors_used());
+
+		// nothing but the one sector of data after the catalogue
+		let image = disc.to_vec().unwrap();
+		assert_eq!(0x300, image.len());
+
+		let copy = dfs::Disc::from_bytes(&image).unwrap();
+		let files: Vec<_> = copy.files()
+			.map(|f| (f.name().to_string(), f.start_sector(), f.content().len(), f.load_addr(), f.exec_addr()))
+			.collect();
+		assert_eq!(vec![
+			("FIRST".into(), Some(2), 0, 0x1100, 0x1101),
+			("DATA".into(), Some(2), 1, 0x1200, 0x1201),
+			("MIDDLE".into(), Some(3), 0, 0x1300, 0x1301),
+			("LAST".into(), Some(3), 0, 0x3_1400, 0x3_1401),
+		], files);
+		assert!(copy.sector_map().iter().all(|s| match s {
+			dfs::SectorState::File(id) => id.to_string() == "$.DATA",
+			_ => true,
+		}));
+	}
+
+	#[test]
+	fn plan_layout() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		assert_eq!(Ok(dfs::Layout { files: vec![], end_sector: 2 }), disc.plan_layout());
+		assert_eq!(Ok(0x200), disc.min_image_len());
+
+		for (name, len) in [(&b"ONE"[..], 0x100), (b"TWO", 0x101), (b"THREE", 1)] {
+			disc.add_file(dfs::File::data(dfs::FileName::try_from(name).unwrap(),
+				AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; len]))).unwrap();
+		}
+		let layout = disc.plan_layout().unwrap();
+		let placements: Vec<_> = layout.files.iter()
+			.map(|p| (p.file.to_string(), p.start_sector, p.sector_count))
+			.collect();
+		assert_eq!(vec![("$.ONE".into(), 2, 1), ("$.TWO".into(), 3, 2), ("$.THREE".into(), 5, 1)],
+			placements);
+		assert_eq!(6, layout.end_sector);
+		assert_eq!(Ok(0x600), disc.min_image_len());
+		assert_eq!(0x600, disc.to_vec().unwrap().len());
+	}
+
+	#[test]
+	fn write_options() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		assert_eq!(dfs::WriteOptions::default(), disc.write_options());
+		disc.add_file(dfs::File::data(dfs::FileName::try_from(&b"ODD"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"abc"))).unwrap();
+
+		let mut image = Vec::new();
+		let opts = dfs::WriteOptions { fill_byte: 0xe5, .. Default::default() };
+		assert_eq!(3, disc.to_image_with(&mut image, opts).unwrap());
+		assert_eq!(0x300, image.len());
+		assert_eq!(b"abc", &image[0x200..0x203]);
+		assert!(image[0x203..].iter().all(|&b| b == 0xe5));
+		assert_eq!(0, image[0x104]);
+
+		let mut image = Vec::new();
+		let opts = dfs::WriteOptions { pad_to_geometry: true, increment_cycle: true, .. opts };
+		disc.to_image_with(&mut image, opts).unwrap();
+		assert_eq!(400 * 256, image.len());
+		assert_eq!(1, image[0x104]);
+	}
+
+	#[test]
+	fn too_large() {
+		use std::borrow::Cow;
+
+		let file = |name: &[u8], len| dfs::File::data(dfs::FileName::try_from(name).unwrap(),
+			AsciiPrintingChar::from(b'B').unwrap(), Cow::Owned(vec![0; len]));
+
+		let mut disc = dfs::Disc::new();
+		disc.add_file(file(b"BIG", 0x40000)).unwrap();
+		let error = disc.to_vec().unwrap_err();
+		assert_eq!(dfs::DFSError::FileTooLarge { key: "B.BIG".into(), len: 0x40000 }, error);
+		assert_eq!("file 'B.BIG' is too large for DFS (262144 bytes)", error.to_string());
+
+		// each fits, but not both together
+		let mut disc = dfs::Disc::new();
+		disc.add_file(file(b"HALF1", 400 * 256)).unwrap();
+		disc.add_file(file(b"HALF2", 400 * 256)).unwrap();
+		assert_eq!(Err(dfs::DFSError::InputTooLarge(802)), disc.to_vec());
+	}
+
+	#[test]
+	fn sector_map() {
+		use dfs::testing::*;
+		use dfs::SectorState;
+
+		let files = [
+			FileSpec::data(b'$', b"BIG", &[0; 0x201]),
+			FileSpec::data(b'A', b"SMALL", b"x"),
+		];
+		let image = make_image(&DiscSpec { files: &files, total_sectors: 10, ..Default::default() });
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+
+		let map: Vec<_> = disc.sector_map().iter().map(|s| match s {
+			SectorState::Catalogue => String::from("C"),
+			SectorState::File(id) => id.to_string(),
+			SectorState::Free => String::from("-"),
+		}).collect();
+		assert_eq!(["C", "C", "$.BIG", "$.BIG", "$.BIG", "A.SMALL", "-", "-", "-", "-"],
+			&map[..]);
+	}
+
+	#[test]
+	fn max_referenced_offset() {
+		use dfs::testing::*;
+
+		let files = [
+			FileSpec::data(b'$', b"FIRST", &[1; 0x150]),
+			FileSpec::data(b'$', b"LAST", &[2; 0x21]),
+		];
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		image.resize(image.len() + 0x300, 0);
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(0x400 + 0x21, disc.max_referenced_offset());
+		assert_eq!(0xdf + 0x300, image.len() - disc.max_referenced_offset());
+
+		assert_eq!(0x200, dfs::Disc::new().max_referenced_offset());
+	}
+
+	#[test]
+	fn scan_deleted() {
+		use dfs::testing::*;
+		use dfs::RecoveredRegion;
+
+		let files = [
+			FileSpec::data(b'$', b"KEEP", b"kept"),
+			FileSpec::data(b'$', b"GONE", &[7; 0x180]),
+			FileSpec::data(b'$', b"ALSO", b"also"),
+		];
+		let mut image = make_image(&DiscSpec { files: &files, total_sectors: 10, ..Default::default() });
+		image.resize(10 * dfs::SECTOR_SIZE, 0xe5);
+
+		let mut disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(Vec::<RecoveredRegion>::new(), disc.scan_deleted(&image));
+
+		let gone = disc.remove_file(&dfs::FileName::try_from(&b"GONE"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR).unwrap();
+		let found = disc.scan_deleted(&image);
+		assert_eq!(vec![RecoveredRegion { start_sector: 3, sector_count: 2 }], found);
+		assert_eq!(gone.content(), &found[0].data(&image)[..0x180]);
+		assert_eq!(0x200, found[0].data(&image).len());
+		assert_eq!(0, RecoveredRegion { start_sector: 20, sector_count: 1 }.data(&image).len());
+	}
+
+	#[test]
+	fn set_cycle() {
+		let mut disc = dfs::Disc::new();
+		assert_eq!(Ok(()), disc.set_cycle(42));
+		assert_eq!(0x42, disc.cycle().into_hex());
+		assert_eq!("42", disc.cycle_display());
+		assert_eq!(Err(BCDError::IntValueTooLarge), disc.set_cycle(100));
+		assert_eq!("42", disc.cycle_display());
+	}
+
+	#[test]
+	fn clone_is_independent() {
+		use std::borrow::Cow;
+		use dfs::testing::*;
+
+		let files = [FileSpec::data(b'$', b"FILE", b"data")];
+		let image = make_image(&DiscSpec { name: b"ORIGINAL", files: &files, ..Default::default() });
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+
+		let mut copy = disc.clone();
+		assert!(copy.files().all(|f| f.is_content_borrowed()));
+		copy.set_name(AsciiPrintingStr::try_from_str("COPY").unwrap()).unwrap();
+		copy.increment_cycle();
+		let name = dfs::FileName::try_from(&b"NEW"[..]).unwrap();
+		copy.add_file(dfs::File::data(name, AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"x")))
+			.unwrap();
+
+		assert_eq!("ORIGINAL", disc.name());
+		assert_eq!(0, disc.cycle().into_u8());
+		assert_eq!(1, disc.files().count());
+		assert_eq!("COPY", copy.name());
+		assert_eq!(2, copy.files().count());
+	}
+
+	#[test]
+	fn disc_eq() {
+		use std::borrow::Cow;
+
+		let mut disc = dfs::Disc::new();
+		disc.set_name(AsciiPrintingStr::try_from_str("EQUAL").unwrap()).unwrap();
+		for name in [&b"ONE"[..], b"TWO"] {
+			let name = dfs::FileName::try_from(name).unwrap();
+			disc.add_file(dfs::File::code(name, AsciiPrintingChar::DOLLAR, 0x1900, 0x1900,
+				Cow::Borrowed(b"code"))).unwrap();
+		}
+
+		let image = disc.to_vec().unwrap();
+		let reread = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(disc, reread);
+
+		// order doesn't matter
+		let mut sorted = reread.clone();
+		sorted.sort_files(dfs::SortKey::LoadAddr);
+		sorted.files.reverse();
+		assert_eq!(disc, sorted);
+
+		let mut changed = reread.clone();
+		let two = dfs::FileName::try_from(&b"TWO"[..]).unwrap();
+		changed.add_file(dfs::File::code(two, AsciiPrintingChar::DOLLAR, 0x1900, 0x1900,
+			Cow::Borrowed(b"edit"))).unwrap();
+		assert_ne!(disc, changed);
+
+		let mut changed = reread.clone();
+		*changed.boot_option_mut() = dfs::BootOption::Exec;
+		assert_ne!(disc, changed);
+	}
+
+	#[test]
+	fn from_reader() {
+		use dfs::testing::*;
+
+		let files = [FileSpec::data(b'$', b"FILE", b"data")];
+		let side = |files| {
+			let mut side = make_image(&DiscSpec { files, total_sectors: 800, ..Default::default() });
+			side.resize(800 * dfs::SECTOR_SIZE, 0);
+			side
+		};
+		// a double-sided 80-track image; its first side is read
+		let image = dfs::interleave_sides(&side(&files), &side(&[]));
+		assert_eq!(400 * 1024, image.len());
+
+		let disc = dfs::Disc::from_reader(&image[..], 400 * 1024).unwrap();
+		assert_eq!(b"data", disc.files().next().unwrap().content());
+		assert!(!disc.files().next().unwrap().is_content_borrowed());
+
+		assert_eq!(Err(dfs::DFSError::InputTooLarge(200 * 1024)),
+			dfs::Disc::from_reader(&image[..], 200 * 1024).map(|_| ()));
+	}
+
+	#[test]
+	fn to_image_round_trip() {
+		use std::borrow::Cow;
+
+		let name = |s: &[u8]| dfs::FileName::try_from(s).unwrap();
+		let dir = |b: u8| AsciiPrintingChar::from(b).unwrap();
+
+		let mut disc = dfs::Disc::new();
+		// twelve characters, so the title is split across both sectors
+		disc.set_name(AsciiPrintingStr::try_from_str("TWELVE CHARS").unwrap()).unwrap();
+		disc.set_cycle(37).unwrap();
+		*disc.boot_option_mut() = dfs::BootOption::Exec;
+
+		// every high bit of every address, and a length over &10000, which
+		// pushes the last file's start sector past 255
+		let files = [
+			dfs::File::new(name(b"!BOOT"), dir(b'$'), 0x1900, 0x8023, true,
+				Cow::Borrowed(b"\x0d\x00\x0a")),
+			dfs::File::new(name(b"HIGH"), dir(b'A'), 0x1_2345, 0x2_6789, false,
+				Cow::Owned(vec![0xaa; 0x1_0001])),
+			dfs::File::new(name(b"TOP"), dir(b'z'), 0x3_ffff, 0x3_0000, true,
+				Cow::Owned((0..=255).collect())),
+			dfs::File::new(name(b"EMPTY"), dir(b'!'), 0, 0, false, Cow::Borrowed(&[])),
+		];
+		for file in files.iter().cloned() {
+			disc.add_file(file).unwrap();
+		}
+
+		let image = disc.to_vec().unwrap();
+		let copy = dfs::Disc::from_bytes(&image).unwrap();
+
+		assert_eq!("TWELVE CHARS", copy.name());
+		assert_eq!(BCD::try_new(37).unwrap(), copy.cycle());
+		assert_eq!(dfs::BootOption::Exec, copy.boot_option());
+		assert_eq!(disc.files().count(), copy.files().count());
+		for (expected, actual) in files.iter().zip(copy.files()) {
+			assert!(expected.deep_eq(actual), "{:?} != {:?}", expected, actual);
+		}
+		assert_eq!(Some(260), copy.files().nth(2).unwrap().start_sector());
+		assert_eq!(disc, copy);
+
+		// writing it out again changes nothing
+		assert_eq!(image, copy.to_vec().unwrap());
+	}
+
+	#[test]
+	fn to_image_keeps_catalogue_order() {
+		use dfs::testing::*;
+
+		// as a real DFS lays out a disc: the newest file first in the
+		// catalogue, but furthest into the disc
+		let files = [
+			FileSpec::data(b'$', b"OLDEST", &[1; 0x100]),
+			FileSpec::data(b'A', b"MIDDLE", &[2; 0x10]),
+			FileSpec::data(b'$', b"NEWEST", &[3; 0x200]),
+		];
+		let mut image = make_image(&DiscSpec { files: &files, ..Default::default() });
+		for half in [0x008, 0x108] {
+			let entries: Vec<u8> = image[half .. half + 24].chunks(8).rev().flatten().copied().collect();
+			image[half .. half + 24].copy_from_slice(&entries);
+		}
+
+		let names = |disc: &dfs::Disc| disc.files().map(|f| f.name().to_string()).collect::<Vec<_>>();
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(vec!["NEWEST", "MIDDLE", "OLDEST"], names(&disc));
+
+		let written = disc.to_vec().unwrap();
+		let copy = dfs::Disc::from_bytes(&written).unwrap();
+		assert_eq!(names(&disc), names(&copy));
+		assert_eq!(disc, copy);
+		assert_eq!(Some(2), copy.files().next().unwrap().start_sector());
+		assert_eq!(written, copy.to_vec().unwrap());
+	}
+
+	#[test]
+	fn to_vec() {
+		use std::convert::TryFrom;
+
+		let disc = dfs::Disc::new_formatted(dfs::Geometry::Tracks40);
+		let mut image = Vec::new();
+		disc.to_image(&mut image).unwrap();
+		assert_eq!(Ok(image.clone()), disc.to_vec());
+		assert_eq!(Ok(image.clone()), Vec::try_from(&disc));
+
+		let back = dfs::Disc::try_from(&image[..]).unwrap();
+		assert_eq!(disc, back);
+		assert_eq!(Err(dfs::DFSError::InputTooSmall(dfs::SECTOR_SIZE * 2)),
+			dfs::Disc::try_from(&image[..0x100]));
+	}
+
 	fn disc_buf_with_name(name: &[u8]) -> [u8 ; dfs::SECTOR_SIZE * 2] {
 		let mut buf = [0u8 ; dfs::SECTOR_SIZE * 2];
 		let parts = name.split_at(8);
@@ -0,0 +1,142 @@
+//! Double-sided (`.dsd`) images, which hold one DFS disc per side.
+//!
+//! Each side is a complete DFS disc with its own catalogue. The image
+//! alternates between them a track (10 sectors) at a time: track 0 of side
+//! 0, then track 0 of side 1, then track 1 of side 0, and so on.
+
+use crate::dfs::*;
+
+const TRACK_SIZE: usize = 10 * SECTOR_SIZE;
+
+/// A double-sided disc: two independent DFS discs, one per side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoubleSidedDisc<'d> {
+	sides: [Disc<'d>; 2],
+}
+
+impl<'d> DoubleSidedDisc<'d> {
+	/// Puts two discs together, `side0` as the side that DFS calls drive 0
+	/// and `side1` as drive 2.
+	pub fn from_sides(side0: Disc<'d>, side1: Disc<'d>) -> DoubleSidedDisc<'d> {
+		DoubleSidedDisc { sides: [side0, side1] }
+	}
+
+	/// Takes the disc apart into its two sides.
+	pub fn split(self) -> (Disc<'d>, Disc<'d>) {
+		let [side0, side1] = self.sides;
+		(side0, side1)
+	}
+
+	pub fn side0(&self) -> &Disc<'d> { &self.sides[0] }
+	pub fn side1(&self) -> &Disc<'d> { &self.sides[1] }
+	pub fn side0_mut(&mut self) -> &mut Disc<'d> { &mut self.sides[0] }
+	pub fn side1_mut(&mut self) -> &mut Disc<'d> { &mut self.sides[1] }
+
+	/// Decodes a double-sided image. Each side is copied out of `src`, so
+	/// the returned disc owns all of its file content.
+	///
+	/// # Errors
+	/// Anything [`Disc::from_bytes`](./struct.Disc.html#method.from_bytes)
+	/// can return for either side. Offsets in errors are into that side,
+	/// not into `src`.
+	pub fn from_bytes(src: &[u8]) -> Result<DoubleSidedDisc<'static>, DFSError> {
+		let (side0, side1) = split_sides(src);
+		Ok(DoubleSidedDisc {
+			sides: [
+				Disc::from_bytes(&side0)?.into_owned(),
+				Disc::from_bytes(&side1)?.into_owned(),
+			],
+		})
+	}
+
+	/// Writes the disc out as an interleaved image, each side written as
+	/// [`Disc::to_image`](./struct.Disc.html#method.to_image) would.
+	///
+	/// # Errors
+	/// As for [`Disc::to_image`](./struct.Disc.html#method.to_image), for
+	/// either side.
+	pub fn to_vec(&self) -> Result<Vec<u8>, DFSError> {
+		Ok(interleave_sides(&self.sides[0].to_vec()?, &self.sides[1].to_vec()?))
+	}
+}
+
+/// Builds a double-sided image from two single-sided ones, alternating a
+/// track from each. If one side is shorter, or either ends part-way
+/// through a track, it is padded with zeroes.
+pub fn interleave_sides(side0: &[u8], side1: &[u8]) -> Vec<u8> {
+	let tracks = (side0.len().max(side1.len()) + TRACK_SIZE - 1) / TRACK_SIZE;
+	let mut image = Vec::with_capacity(tracks * TRACK_SIZE * 2);
+	for track in 0..tracks {
+		for side in [side0, side1] {
+			let start = (track * TRACK_SIZE).min(side.len());
+			let end = (start + TRACK_SIZE).min(side.len());
+			image.extend_from_slice(&side[start..end]);
+			image.resize(image.len() + TRACK_SIZE - (end - start), 0);
+		}
+	}
+	image
+}
+
+/// Takes a double-sided image apart into its two sides. This is the
+/// reverse of [`interleave_sides`](./fn.interleave_sides.html), except
+/// that a short last track is left short.
+pub fn split_sides(image: &[u8]) -> (Vec<u8>, Vec<u8>) {
+	let mut sides = (Vec::with_capacity(image.len() / 2), Vec::with_capacity(image.len() / 2));
+	for (i, track) in image.chunks(TRACK_SIZE).enumerate() {
+		match i & 1 {
+			0 => sides.0.extend_from_slice(track),
+			_ => sides.1.extend_from_slice(track),
+		}
+	}
+	sides
+}
+
+#[cfg(test)]
+mod test {
+	use std::borrow::Cow;
+
+	use super::*;
+	use crate::support::*;
+
+	#[test]
+	fn interleave() {
+		let side0 = vec![0u8; 20 * SECTOR_SIZE];
+		let side1 = vec![1u8; 20 * SECTOR_SIZE];
+		let image = interleave_sides(&side0, &side1);
+		assert_eq!(40 * SECTOR_SIZE, image.len());
+		for (track, data) in image.chunks(TRACK_SIZE).enumerate() {
+			assert!(data.iter().all(|&b| b as usize == track & 1));
+		}
+		assert_eq!((side0, side1), split_sides(&image));
+
+		// the shorter side is padded out to the longer one
+		let image = interleave_sides(&[1; 3], &[2; TRACK_SIZE + 1]);
+		assert_eq!(4 * TRACK_SIZE, image.len());
+		assert_eq!(&[1, 1, 1, 0], &image[..4]);
+		assert_eq!(&[2, 0], &image[3 * TRACK_SIZE .. 3 * TRACK_SIZE + 2]);
+	}
+
+	#[test]
+	fn join_and_split() {
+		fn disc<'a>(title: &str, file: &[u8], content: &'a [u8]) -> Disc<'a> {
+			let mut disc = Disc::new();
+			disc.set_name(AsciiPrintingStr::try_from_str(title).unwrap()).unwrap();
+			disc.add_file(File::data(FileName::try_from(file).unwrap(), AsciiPrintingChar::DOLLAR,
+				Cow::Borrowed(content))).unwrap();
+			disc
+		}
+		// side 0 runs past its first track, so the sides have to be taken
+		// apart track by track to get it back
+		let long = vec![0xaa; TRACK_SIZE * 2];
+		let side0 = disc("SIDE ZERO", b"LONG", &long);
+		let side1 = disc("SIDE ONE", b"SHORT", b"short");
+
+		let image = DoubleSidedDisc::from_sides(side0.clone(), side1.clone()).to_vec().unwrap();
+		assert_eq!(6 * TRACK_SIZE, image.len());
+
+		let (back0, back1) = DoubleSidedDisc::from_bytes(&image).unwrap().split();
+		assert_eq!(side0, back0);
+		assert_eq!(side1, back1);
+		assert_eq!("SIDE ONE", back1.name());
+	}
+}
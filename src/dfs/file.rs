@@ -9,11 +9,61 @@ use ascii::AsciiStr;
 
 pub type FileName = AsciiName<7>;
 
+impl FileName {
+	/// Checks if a real DFS could address a file with this name.
+	///
+	/// Names containing wildcards (`*`, `#`), path separators (`.`, `:`),
+	/// quotes or spaces can exist in a catalogue, but can't be typed in a
+	/// command.
+	pub fn is_addressable(&self) -> bool {
+		!self.iter().copied().any(is_reserved_in_name)
+	}
+}
+
+/// The conventional address (`&FFFF`, with both high bits set) for files
+/// with no fixed place in memory, such as text and data files.
+pub const ADDR_NONE: u32 = 0x3ffff;
+
+/// The line ending [`File::as_text`](./struct.File.html#method.as_text)
+/// writes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, enum_utils::FromStr)]
+#[enumeration(case_insensitive, rename_all = "kebab-case")]
+pub enum NewlineStyle {
+	/// `\n`, as Unix and most modern editors expect.
+	Lf,
+	/// `\r\n`, as Windows expects.
+	Crlf,
+	/// `\r` alone, as the BBC Micro writes it.
+	Cr,
+}
+
+impl NewlineStyle {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Lf   => "lf",
+			Self::Crlf => "crlf",
+			Self::Cr   => "cr",
+		}
+	}
+
+	fn ending(self) -> &'static str {
+		match self {
+			Self::Lf   => "\n",
+			Self::Crlf => "\r\n",
+			Self::Cr   => "\r",
+		}
+	}
+}
+
+pub(super) fn is_reserved_in_name(c: AsciiPrintingChar) -> bool {
+	matches!(c.as_byte(), b'*' | b'#' | b'.' | b':' | b'"' | b' ')
+}
+
 /// A representation of a file in a DFS disc.
 ///
 /// The identity of a `File` (equality, hashing etc.) is determined by the
 /// file's name and directory.
-#[derive(PartialEq, Eq)]
+#[derive(Clone)]
 pub struct File<'d> {
 	/// The name of the file, including directory.
 	name: Key,
@@ -25,6 +75,10 @@ pub struct File<'d> {
 	is_locked: bool,
 	/// The content of the file.
 	content: Cow<'d, [u8]>,
+	/// Where the file's data starts on disc, if known.
+	start_sector: Option<u16>,
+	/// Where the file was in the catalogue of the image it was read from.
+	catalogue_index: Option<u8>,
 }
 
 impl<'d> File<'d> {
@@ -38,9 +92,25 @@ impl<'d> File<'d> {
 			exec_addr,
 			is_locked,
 			content,
+			start_sector: None,
+			catalogue_index: None,
 		}
 	}
 
+	/// Creates an unlocked data file, with a load address of 0 and no
+	/// execution address.
+	pub fn data(name: FileName, dir: AsciiPrintingChar, content: Cow<'d, [u8]>) -> File<'d> {
+		File::new(name, dir, 0, ADDR_NONE, false, content)
+	}
+
+	/// Creates an unlocked file of machine code, loaded and run at the given
+	/// addresses.
+	pub fn code(name: FileName, dir: AsciiPrintingChar,
+		load_addr: u32, exec_addr: u32,
+		content: Cow<'d, [u8]>) -> File<'d> {
+		File::new(name, dir, load_addr, exec_addr, false, content)
+	}
+
 	pub fn dir(&self) -> AsciiPrintingChar {
 		self.name.dir
 	}
@@ -59,16 +129,169 @@ impl<'d> File<'d> {
 	pub fn load_addr(&self) -> u32 { self.load_addr }
 	pub fn exec_addr(&self) -> u32 { self.exec_addr }
 	pub fn is_locked(&self) -> bool { self.is_locked }
+	pub fn set_load_addr(&mut self, addr: u32) { self.load_addr = addr; }
+	pub fn set_exec_addr(&mut self, addr: u32) { self.exec_addr = addr; }
+
+	/// Replaces the content of the file.
+	pub fn set_content(&mut self, content: Cow<'d, [u8]>) { self.content = content; }
+
+	/// Checks if the file has no execution address (its low 16 bits are
+	/// `&FFFF`), so is not meant to be run and has no fixed place in memory.
+	/// Text files conventionally look like this.
+	pub fn is_relocatable(&self) -> bool { self.exec_addr & 0xffff == 0xffff }
 	pub fn content<'s>(&'s self) -> &'s [u8] where 'd: 's {
 		self.content.borrow()
 	}
 
+	/// The content of the file. This is the same as
+	/// [`content`](#method.content), without the extra lifetime bound.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// use std::borrow::Cow;
+	/// use dfsdisc::dfs;
+	/// use dfsdisc::support::AsciiPrintingChar;
+	///
+	/// fn total_len<'a, I: IntoIterator<Item = &'a dfs::File<'a>>>(files: I) -> usize {
+	///     files.into_iter().map(|f| f.bytes().len()).sum()
+	/// }
+	///
+	/// let name = dfs::FileName::try_from(&b"HELLO"[..]).unwrap();
+	/// let file = dfs::File::new(name, AsciiPrintingChar::DOLLAR, 0, 0, false,
+	///     Cow::Borrowed(&b"hello"[..]));
+	/// assert_eq!(5, total_len(&[file]));
+	/// ```
+	pub fn bytes(&self) -> &[u8] { &self.content }
+
+	/// The content of the file, showing whether it is borrowed from a disc
+	/// image or owned.
+	pub fn content_cow(&self) -> &Cow<'d, [u8]> { &self.content }
+
+	/// Checks if the file's content is borrowed, usually from the disc image
+	/// it was read from, rather than owned by the file.
+	pub fn is_content_borrowed(&self) -> bool {
+		matches!(self.content, Cow::Borrowed(_))
+	}
+
+	/// Makes the file own its content, copying it if it was borrowed.
+	///
+	/// The file keeps its lifetime, so this doesn't free it from the source
+	/// image on its own, but nothing it holds points there any more.
+	pub fn into_owned_content(&mut self) {
+		self.content.to_mut();
+	}
+
+	/// The sector where this file's data starts on disc. This is set when
+	/// the file is read from an image, or placed by
+	/// [`Disc::place_files`](./struct.Disc.html#method.place_files); files
+	/// made in memory have none until then. Writing a disc out may move the
+	/// file elsewhere.
+	pub fn start_sector(&self) -> Option<u16> { self.start_sector }
+
+	/// Where the file was listed in the catalogue of the image it was read
+	/// from, counting from 0, or `None` if it wasn't read from an image.
+	/// This stays the same as the disc's catalogue is changed or sorted.
+	pub fn catalogue_index(&self) -> Option<u8> { self.catalogue_index }
+
+	/// The file's content in sector-sized chunks, as it sits on disc. The
+	/// last chunk may be short; an empty file has none.
+	pub fn sectors(&self) -> impl Iterator<Item = &[u8]> + '_ {
+		self.content.chunks(super::SECTOR_SIZE)
+	}
+
+	/// The CRC of the file's content, as Acorn tools compute it. See
+	/// [`crc16_ccitt`](../support/fn.crc16_ccitt.html).
+	pub fn crc16(&self) -> u16 { crc16_ccitt(&self.content) }
+
+	/// The "busy byte" that the catalogue holds for this file, at offset 6
+	/// of its entry in the second sector.
+	///
+	/// Standard DFS uses all of it for four 2-bit fields: the top bits of the
+	/// execution address (bits 6–7), length (4–5), load address (2–3) and
+	/// start sector (0–1). Those are all kept in the file as it's read, so
+	/// this is worked out from them, and round-trips through
+	/// [`to_image`](./struct.Disc.html#method.to_image) unchanged. A file
+	/// with no start sector has 0 in bits 0–1.
+	pub fn raw_attributes(&self) -> u8 {
+		super::CatalogueEntry::for_file(self, self.start_sector.unwrap_or(0)).busy_byte()
+	}
+
+	/// The file's content as editable text, with each line ending (`CR`,
+	/// `LF`, `CR LF` or `LF CR`) written as `newline`.
+	///
+	/// Other bytes that aren't printable ASCII are escaped the way `*KEY`
+	/// and GSTRANS write them: control codes as `|@` to `|_` (`|?` for
+	/// `DEL`), and top-bit-set bytes with a `|!` prefix. A literal `|` is
+	/// written as `||`. Tabs are kept as they are.
+	pub fn as_text(&self, newline: NewlineStyle) -> Cow<'_, str> {
+		let plain = |b: u8| b == b'\t' || ((b' '..=b'~').contains(&b) && b != b'|');
+		if self.content.iter().all(|&b| plain(b)) {
+			// all plain ASCII, so this can't fail
+			return Cow::Borrowed(std::str::from_utf8(&self.content).unwrap_or_default());
+		}
+
+		let mut text = String::with_capacity(self.content.len());
+		let mut bytes = self.content.iter().copied().peekable();
+		while let Some(b) = bytes.next() {
+			match b {
+				b'\r' | b'\n' => {
+					// a pair of different line-end bytes is one line ending
+					let other = b ^ (b'\r' ^ b'\n');
+					if bytes.peek() == Some(&other) {
+						bytes.next();
+					}
+					text.push_str(newline.ending());
+				},
+				_ => push_escaped(&mut text, b, plain),
+			}
+		}
+		Cow::Owned(text)
+	}
+
 	pub fn lock(&mut self) { self.is_locked = true; }
 	pub fn unlock(&mut self) { self.is_locked = false; }
 
 
 	pub(super) fn key(&self) -> &Key { &self.name }
 
+	/// Checks if two files are fully identical: name, directory, addresses,
+	/// lock state and content. Where their data lies on disc is not
+	/// compared.
+	///
+	/// `==` on files only compares their names and directories.
+	pub fn deep_eq(&self, other: &File) -> bool {
+		self.name == other.name
+			&& self.load_addr == other.load_addr
+			&& self.exec_addr == other.exec_addr
+			&& self.is_locked == other.is_locked
+			&& self.content() == other.content()
+	}
+
+	/// Copies the content if it's borrowed, so the file no longer depends on
+	/// its source. Unlike [`into_owned_content`](#method.into_owned_content),
+	/// this frees the file of the source's lifetime, so it can outlive the
+	/// image it was read from.
+	pub fn into_owned(self) -> File<'static> {
+		File {
+			name: self.name,
+			load_addr: self.load_addr,
+			exec_addr: self.exec_addr,
+			is_locked: self.is_locked,
+			content: Cow::Owned(self.content.into_owned()),
+			start_sector: self.start_sector,
+			catalogue_index: self.catalogue_index,
+		}
+	}
+
+	pub(super) fn set_start_sector(&mut self, sector: u16) {
+		self.start_sector = Some(sector);
+	}
+
+	pub(super) fn set_catalogue_index(&mut self, index: u8) {
+		self.catalogue_index = Some(index);
+	}
+
 }
 
 impl<'d> fmt::Display for File<'d> {
@@ -94,6 +317,12 @@ impl<'d> Hash for File<'d> {
 	fn hash<H: Hasher>(&self, state: &mut H) { self.name.hash(state); }
 }
 
+impl<'d> PartialEq for File<'d> {
+	fn eq(&self, other: &Self) -> bool { self.name == other.name }
+}
+
+impl<'d> Eq for File<'d> {}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub(super) struct Key {
 	pub name: AsciiName<7>,
@@ -129,3 +358,109 @@ impl Ord for Key {
 		}
 	}
 }
+
+fn push_escaped(text: &mut String, b: u8, plain: impl Fn(u8) -> bool) {
+	if b >= 0x80 {
+		text.push_str("|!");
+	}
+	match b & 0x7f {
+		b'|' => text.push_str("||"),
+		c if plain(c) => text.push(c as char),
+		0x7f => text.push_str("|?"),
+		c => { text.push('|'); text.push((c + b'@') as char); },
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn name() -> FileName { FileName::try_from(&b"FILE"[..]).unwrap() }
+
+	#[test]
+	fn relocatable() {
+		let text = File::new(name(), AsciiPrintingChar::DOLLAR, 0, 0xffff, false,
+			Cow::Borrowed(b"10 PRINT\r"));
+		assert!(text.is_relocatable());
+
+		let data = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Borrowed(&[]));
+		assert!(data.is_relocatable());
+		assert_eq!((0, ADDR_NONE), (data.load_addr(), data.exec_addr()));
+
+		let code = File::code(name(), AsciiPrintingChar::DOLLAR, 0x1900, 0x1900,
+			Cow::Borrowed(&[]));
+		assert!(!code.is_relocatable());
+	}
+
+	#[test]
+	fn deep_eq() {
+		let a = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"abc"));
+		let b = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Owned(b"abc".to_vec()));
+		assert!(a == b && a.deep_eq(&b));
+
+		let different = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"xyz"));
+		assert!(a == different);
+		assert!(!a.deep_eq(&different));
+
+		let mut locked = a.clone();
+		locked.lock();
+		assert!(a == locked);
+		assert!(!a.deep_eq(&locked));
+	}
+
+	#[test]
+	fn as_text() {
+		let text = File::data(name(), AsciiPrintingChar::DOLLAR,
+			Cow::Borrowed(b"FIRST\rSECOND\n\rTHIRD\r\n\rA|B\x07\x1b\xc1\r"));
+		assert_eq!("FIRST\nSECOND\nTHIRD\n\nA||B|G|[|!A\n", text.as_text(NewlineStyle::Lf));
+		assert_eq!("FIRST\r\nSECOND\r\nTHIRD\r\n\r\nA||B|G|[|!A\r\n",
+			text.as_text(NewlineStyle::Crlf));
+		assert_eq!("FIRST\rSECOND\rTHIRD\r\rA||B|G|[|!A\r", text.as_text(NewlineStyle::Cr));
+
+		let plain = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"no\tbreaks"));
+		assert!(matches!(plain.as_text(NewlineStyle::Lf), Cow::Borrowed("no\tbreaks")));
+		assert_eq!(Ok(NewlineStyle::Crlf), "CRLF".parse());
+	}
+
+	#[test]
+	fn sectors() {
+		let file = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Owned(vec![0; 257]));
+		let lens: Vec<usize> = file.sectors().map(<[u8]>::len).collect();
+		assert_eq!(vec![256, 1], lens);
+
+		let empty = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Borrowed(&[]));
+		assert_eq!(0, empty.sectors().count());
+	}
+
+	#[test]
+	fn into_owned() {
+		use crate::dfs::testing::*;
+
+		let file = {
+			let image = make_image(&DiscSpec {
+				files: &[FileSpec { dir: b'$', name: b"KEEP", load_addr: 0x1900, exec_addr: 0x1900,
+					locked: true, content: b"kept" }],
+				.. DiscSpec::default()
+			});
+			let disc = crate::dfs::Disc::from_bytes(&image).unwrap();
+			let file = disc.files().next().unwrap().clone();
+			assert!(file.is_content_borrowed());
+			file.into_owned()
+		};
+
+		assert!(!file.is_content_borrowed());
+		assert_eq!(b"kept", file.content());
+		assert_eq!((0x1900, true, Some(2)), (file.load_addr(), file.is_locked(), file.start_sector()));
+	}
+
+	#[test]
+	fn owned_content() {
+		let mut file = File::data(name(), AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"abc"));
+		assert!(file.is_content_borrowed());
+		file.into_owned_content();
+		assert!(!file.is_content_borrowed());
+		assert_eq!(b"abc", file.content());
+		file.into_owned_content();
+		assert!(!file.is_content_borrowed());
+	}
+}
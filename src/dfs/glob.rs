@@ -0,0 +1,91 @@
+//! DFS-style wildcard matching of file names.
+//!
+//! Patterns take the same `D.NAME` form as file names on the command line,
+//! with the directory defaulting to `$` if left out. In either part, `#`
+//! matches any one character, and `*` matches any run of characters
+//! (including none). Matching ignores case, as DFS does.
+
+use crate::dfs::FileName;
+use crate::support::AsciiPrintingChar;
+
+/// Checks whether a pattern contains wildcards.
+pub fn is_pattern(pattern: &str) -> bool {
+	pattern.contains(['*', '#'])
+}
+
+/// Checks whether the file `dir.name` matches `pattern`.
+///
+/// # Examples
+///
+/// ```rust
+/// use dfsdisc::dfs::{glob, FileName};
+/// use dfsdisc::support::AsciiPrintingChar;
+///
+/// let name = FileName::try_from(&b"FOO"[..]).unwrap();
+/// assert!(glob::matches("*.F#O", AsciiPrintingChar::DOLLAR, &name));
+/// assert!(glob::matches("F*", AsciiPrintingChar::DOLLAR, &name));
+/// assert!(!glob::matches("B.*", AsciiPrintingChar::DOLLAR, &name));
+/// ```
+pub fn matches(pattern: &str, dir: AsciiPrintingChar, name: &FileName) -> bool {
+	let pattern = pattern.as_bytes();
+	let (dir_pattern, name_pattern) = match pattern {
+		[_, b'.', ..] => (&pattern[..1], &pattern[2..]),
+		_ => (&b"$"[..], pattern),
+	};
+
+	field_matches(dir_pattern, &[dir.as_byte()])
+		&& field_matches(name_pattern, name.as_bytes())
+}
+
+fn field_matches(pattern: &[u8], field: &[u8]) -> bool {
+	match (pattern.split_first(), field.split_first()) {
+		(None, None) => true,
+		(Some((b'*', rest)), _) =>
+			field_matches(rest, field)
+			|| (!field.is_empty() && field_matches(pattern, &field[1..])),
+		(Some((b'#', rest)), Some((_, field_rest))) => field_matches(rest, field_rest),
+		(Some((p, rest)), Some((f, field_rest))) =>
+			p.eq_ignore_ascii_case(f) && field_matches(rest, field_rest),
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn check(pattern: &str, path: &str) -> bool {
+		let dir = AsciiPrintingChar::from(path.as_bytes()[0]).unwrap();
+		let name = FileName::try_from(&path.as_bytes()[2..]).unwrap();
+		matches(pattern, dir, &name)
+	}
+
+	#[test]
+	fn wildcards() {
+		assert!(check("*.*", "$.FOO"));
+		assert!(check("*.*", "B.X"));
+
+		assert!(check("$.F#O", "$.FOO"));
+		assert!(check("$.F#O", "$.FAO"));
+		assert!(!check("$.F#O", "$.FO"));
+		assert!(!check("$.F#O", "$.FOOO"));
+
+		assert!(check("#.*", "A.ANY"));
+		assert!(check("#.*", "$.A"));
+
+		assert!(check("B.*", "B.STUFF"));
+		assert!(!check("B.*", "C.STUFF"));
+		assert!(check("*", "$.ANY"));
+		assert!(!check("*", "A.ANY"));
+		assert!(check("$.*O", "$.FOO"));
+		assert!(check("$.foo", "$.FOO"));
+		assert!(!check("$.FOO", "$.FOOD"));
+	}
+
+	#[test]
+	fn patterns() {
+		assert!(is_pattern("*.*"));
+		assert!(is_pattern("$.F#O"));
+		assert!(!is_pattern("$.FOO"));
+	}
+}
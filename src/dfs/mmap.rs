@@ -0,0 +1,133 @@
+//! Reading disc images through a memory map, for tools that open a lot of
+//! them and don't want to copy each one.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::dfs::*;
+
+/// A read-only, private mapping of a whole file.
+struct Map {
+	ptr: *mut libc::c_void,
+	len: usize,
+}
+
+// The mapping is never written through, so it can be shared like a `&[u8]`.
+unsafe impl Send for Map {}
+unsafe impl Sync for Map {}
+
+impl Map {
+	fn of(file: &File, len: usize) -> io::Result<Map> {
+		let ptr = unsafe {
+			libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE,
+				file.as_raw_fd(), 0)
+		};
+		match ptr {
+			libc::MAP_FAILED => Err(io::Error::last_os_error()),
+			ptr => Ok(Map { ptr, len }),
+		}
+	}
+}
+
+impl Deref for Map {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+	}
+}
+
+impl Drop for Map {
+	fn drop(&mut self) {
+		unsafe { libc::munmap(self.ptr, self.len); }
+	}
+}
+
+/// A disc read from a memory-mapped image, as returned by
+/// [`Disc::from_mmap`]. Its files borrow their content from the mapping,
+/// which lives as long as this does.
+///
+/// [`Disc::from_mmap`]: ./struct.Disc.html#method.from_mmap
+pub struct MappedDisc {
+	// declared first, so it is dropped before the mapping it borrows from
+	disc: Disc<'static>,
+	map: Map,
+}
+
+impl MappedDisc {
+	pub fn disc(&self) -> &Disc<'_> { &self.disc }
+
+	/// The whole mapped image.
+	pub fn image(&self) -> &[u8] { &self.map }
+}
+
+impl std::fmt::Debug for MappedDisc {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("MappedDisc").field("disc", &self.disc).field("len", &self.map.len).finish()
+	}
+}
+
+impl Disc<'static> {
+	/// Maps the image at `path` into memory and decodes it as
+	/// [`from_bytes`](#method.from_bytes) would, without copying any of it.
+	/// Only available with the `mmap` feature, on Unix.
+	///
+	/// # Safety
+	/// The file must not be changed, by this process or any other, while the
+	/// returned `MappedDisc` is alive. The mapping follows changes to the
+	/// file, so file content that has already been read could change under
+	/// the disc, or vanish if the file is truncated.
+	///
+	/// # Errors
+	/// * [`DFSError::Io`][DFSError]: the file couldn't be opened or mapped.
+	/// * Anything `from_bytes` can return.
+	///
+	/// [DFSError]: ./enum.DFSError.html
+	pub unsafe fn from_mmap(path: &Path) -> Result<MappedDisc, DFSError> {
+		let file = File::open(path)?;
+		let len = file.metadata()?.len() as usize;
+		// a zero-length mapping is an error, and anything this short can't
+		// be a disc anyway
+		if len < SECTOR_SIZE * 2 {
+			return Err(DFSError::InputTooSmall(SECTOR_SIZE * 2));
+		}
+
+		let map = Map::of(&file, len)?;
+		// the mapping doesn't move when `map` does, so the disc can borrow
+		// from it for as long as the two are kept together
+		let image: &'static [u8] = std::slice::from_raw_parts(map.ptr as *const u8, map.len);
+		let disc = Disc::from_bytes(image)?;
+		Ok(MappedDisc { disc, map })
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::dfs;
+	use crate::dfs::testing::*;
+
+	#[test]
+	fn from_mmap() {
+		let files = [FileSpec { dir: b'$', name: b"MAPPED", load_addr: 0x1900, exec_addr: 0x1900,
+			locked: false, content: b"mapped content" }];
+		let image = make_image(&DiscSpec { name: b"MMAP", files: &files, ..Default::default() });
+		let path = std::env::temp_dir().join(format!("dfsdisc-mmap-{}.ssd", std::process::id()));
+		std::fs::write(&path, &image).unwrap();
+
+		let mapped = unsafe { dfs::Disc::from_mmap(&path) }.unwrap();
+		assert_eq!(&image[..], mapped.image());
+		assert_eq!(dfs::Disc::from_bytes(&image).unwrap(), *mapped.disc());
+		let file = mapped.disc().files().next().unwrap();
+		assert_eq!(b"mapped content", file.content());
+		assert_eq!(mapped.image().as_ptr_range().start as usize + 0x200, file.content().as_ptr() as usize);
+
+		std::fs::write(&path, b"short").unwrap();
+		assert_eq!(dfs::DFSError::InputTooSmall(0x200),
+			unsafe { dfs::Disc::from_mmap(&path) }.unwrap_err());
+		std::fs::remove_file(&path).unwrap();
+		assert!(matches!(unsafe { dfs::Disc::from_mmap(&path) }, Err(dfs::DFSError::Io(_))));
+	}
+}
@@ -1,7 +1,18 @@
 //! Types and conversions for DFS disc images.
 
+pub mod addr;
+mod catalogue;
+pub mod ddos;
+mod diff;
 mod disc;
+mod double_sided;
 mod file;
+pub mod glob;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
 /// Sector size in all known DFS implementations.
 pub const SECTOR_SIZE: usize = 256;
@@ -16,6 +27,15 @@ pub enum DFSError {
 	InputTooLarge(usize),
 	InvalidDiscData(usize),
 	DuplicateFileName(String),
+	/// The named file is longer than a catalogue entry can record.
+	FileTooLarge { key: String, len: usize },
+	/// The named file is locked, so can't be removed or replaced.
+	FileLocked(String),
+	/// The catalogue already holds as many files as it can.
+	CatalogueFull { max: u8 },
+	/// The image has a different catalogue layout, which needs a different
+	/// reader.
+	WrongCatalogueKind(CatalogueKind),
 	Io(std::io::Error),
 }
 
@@ -27,16 +47,112 @@ impl PartialEq for DFSError {
 			(Self::InputTooLarge(a), Self::InputTooLarge(b)) => a == b,
 			(Self::InvalidDiscData(a), Self::InvalidDiscData(b)) => a == b,
 			(Self::DuplicateFileName(a), Self::DuplicateFileName(b)) => a == b,
+			(Self::FileTooLarge { key: a, len: m }, Self::FileTooLarge { key: b, len: n }) =>
+				a == b && m == n,
+			(Self::FileLocked(a), Self::FileLocked(b)) => a == b,
+			(Self::CatalogueFull { max: a }, Self::CatalogueFull { max: b }) => a == b,
+			(Self::WrongCatalogueKind(a), Self::WrongCatalogueKind(b)) => a == b,
 			_ => false,
 		}
 	}
 }
 
+/// The kind of a [`DFSError`](./enum.DFSError.html), without its details.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DFSErrorKind {
+	InvalidValue,
+	InputTooSmall,
+	InputTooLarge,
+	InvalidDiscData,
+	DuplicateFileName,
+	FileTooLarge,
+	FileLocked,
+	CatalogueFull,
+	WrongCatalogueKind,
+	Io,
+}
+
+impl DFSError {
+	pub fn kind(&self) -> DFSErrorKind {
+		match self {
+			Self::InvalidValue => DFSErrorKind::InvalidValue,
+			Self::InputTooSmall(_) => DFSErrorKind::InputTooSmall,
+			Self::InputTooLarge(_) => DFSErrorKind::InputTooLarge,
+			Self::InvalidDiscData(_) => DFSErrorKind::InvalidDiscData,
+			Self::DuplicateFileName(_) => DFSErrorKind::DuplicateFileName,
+			Self::FileTooLarge { .. } => DFSErrorKind::FileTooLarge,
+			Self::FileLocked(_) => DFSErrorKind::FileLocked,
+			Self::CatalogueFull { .. } => DFSErrorKind::CatalogueFull,
+			Self::WrongCatalogueKind(_) => DFSErrorKind::WrongCatalogueKind,
+			Self::Io(_) => DFSErrorKind::Io,
+		}
+	}
+
+	/// The offset into the disc image where the bad data was found, for
+	/// errors that have one.
+	pub fn offset(&self) -> Option<usize> {
+		match *self {
+			Self::InvalidDiscData(offset) => Some(offset),
+			_ => None,
+		}
+	}
+}
+
+impl std::fmt::Display for DFSError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Self::InvalidValue => f.write_str("invalid value"),
+			Self::InputTooSmall(n) => write!(f, "input too small; need at least {} bytes", n),
+			Self::InputTooLarge(n) => write!(f, "input too large: {}", n),
+			Self::InvalidDiscData(offset) => write!(f, "invalid disc data at 0x{:03x}", offset),
+			Self::DuplicateFileName(name) => write!(f, "file name '{}' appears twice", name),
+			Self::FileTooLarge { key, len } =>
+				write!(f, "file '{}' is too large for DFS ({} bytes)", key, len),
+			Self::FileLocked(name) => write!(f, "file '{}' is locked", name),
+			Self::CatalogueFull { max } => write!(f, "disc catalogue is full (max {} files)", max),
+			Self::WrongCatalogueKind(kind) => write!(f, "disc has a different catalogue layout ({:?})", kind),
+			Self::Io(e) => write!(f, "I/O error: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for DFSError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
 impl From<std::io::Error> for DFSError {
 	fn from(src: std::io::Error) -> DFSError {
 		DFSError::Io(src)
 	}
 }
 
+pub use self::catalogue::{looks_like_dfs, Catalogue, CatalogueEntry, MAX_FILES};
+pub use self::ddos::CatalogueKind;
+pub use self::diff::*;
 pub use self::disc::*;
+pub use self::double_sided::*;
 pub use self::file::*;
+#[cfg(all(feature = "mmap", unix))]
+pub use self::mmap::MappedDisc;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn error_offset() {
+		let e = DFSError::InvalidDiscData(0x107);
+		assert_eq!(Some(0x107), e.offset());
+		assert_eq!(DFSErrorKind::InvalidDiscData, e.kind());
+		assert_eq!("invalid disc data at 0x107", e.to_string());
+
+		let e = DFSError::InputTooSmall(512);
+		assert_eq!(None, e.offset());
+		assert_eq!(DFSErrorKind::InputTooSmall, e.kind());
+	}
+}
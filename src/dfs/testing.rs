@@ -0,0 +1,166 @@
+//! Helpers for building well-formed disc images in tests.
+//!
+//! This module is only available with the `test-util` feature. The images
+//! are encoded directly from a [`DiscSpec`], without going through
+//! [`Disc::to_image`](../struct.Disc.html#method.to_image), so they can be
+//! used to check both the parser and the serialiser.
+
+use crate::dfs::{BootOption, SECTOR_SIZE};
+use crate::support::SectorMathExt;
+
+/// A high-level description of a single file in a [`DiscSpec`].
+#[derive(Debug, Clone, Copy)]
+pub struct FileSpec<'a> {
+	pub dir: u8,
+	pub name: &'a [u8],
+	pub load_addr: u32,
+	pub exec_addr: u32,
+	pub locked: bool,
+	pub content: &'a [u8],
+}
+
+impl<'a> FileSpec<'a> {
+	/// An unlocked file with load and execution addresses of 0.
+	pub const fn data(dir: u8, name: &'a [u8], content: &'a [u8]) -> FileSpec<'a> {
+		FileSpec { dir, name, load_addr: 0, exec_addr: 0, locked: false, content }
+	}
+}
+
+/// A high-level description of a disc image.
+///
+/// Files are laid out in the order given, starting at sector 2.
+#[derive(Debug, Clone, Copy)]
+pub struct DiscSpec<'a> {
+	pub name: &'a [u8],
+	/// Cycle count, in decimal.
+	pub cycle: u8,
+	pub boot_option: BootOption,
+	/// Declared disc size. This is raised if the files need more space.
+	pub total_sectors: u16,
+	pub files: &'a [FileSpec<'a>],
+}
+
+impl<'a> Default for DiscSpec<'a> {
+	fn default() -> Self {
+		DiscSpec {
+			name: b"",
+			cycle: 0,
+			boot_option: BootOption::None,
+			total_sectors: 400,
+			files: &[],
+		}
+	}
+}
+
+/// Builds a disc image from `spec`.
+///
+/// The image is only as long as it needs to be to hold the catalogue and
+/// every file's data, rounded up to a whole sector.
+///
+/// # Panics
+/// Panics if `spec` can't be represented on a DFS disc: a name is too long,
+/// there are more than 31 files, or the cycle count is over 99.
+pub fn make_image(spec: &DiscSpec) -> Vec<u8> {
+	assert!(spec.name.len() <= 12, "disc name too long");
+	assert!(spec.files.len() <= 31, "too many files");
+	assert!(spec.cycle <= 99, "cycle count too large");
+
+	let mut image = vec![0u8; SECTOR_SIZE * 2];
+
+	let mut title = [b' '; 12];
+	title[..spec.name.len()].copy_from_slice(spec.name);
+	image[0x000..0x008].copy_from_slice(&title[..8]);
+	image[0x100..0x104].copy_from_slice(&title[8..]);
+
+	let mut sector = 2u32;
+	for (i, file) in spec.files.iter().enumerate() {
+		assert!(file.name.len() <= 7, "file name too long");
+		let len = file.content.len() as u32;
+
+		let entry = &mut image[(i + 1) * 8 .. (i + 2) * 8];
+		entry.fill(b' ');
+		entry[..file.name.len()].copy_from_slice(file.name);
+		entry[7] = file.dir | if file.locked { 0x80 } else { 0 };
+
+		let entry = &mut image[0x100 + (i + 1) * 8 .. 0x100 + (i + 2) * 8];
+		entry[0..2].copy_from_slice(&(file.load_addr as u16).to_le_bytes());
+		entry[2..4].copy_from_slice(&(file.exec_addr as u16).to_le_bytes());
+		entry[4..6].copy_from_slice(&(len as u16).to_le_bytes());
+		entry[6] = (((file.exec_addr >> 16) & 3) << 6
+			| ((len >> 16) & 3) << 4
+			| ((file.load_addr >> 16) & 3) << 2
+			| (sector >> 8) & 3) as u8;
+		entry[7] = sector as u8;
+
+		image.extend_from_slice(file.content);
+		sector = image.len().sectors() as u32;
+		image.resize(sector as usize * SECTOR_SIZE, 0);
+	}
+
+	let total_sectors = (spec.total_sectors as u32).max(sector);
+	image[0x104] = (spec.cycle / 10) << 4 | (spec.cycle % 10);
+	image[0x105] = (spec.files.len() * 8) as u8;
+	image[0x106] = (spec.boot_option as u8) << 4 | ((total_sectors >> 8) & 3) as u8;
+	image[0x107] = total_sectors as u8;
+
+	image
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::dfs;
+
+	static FILES: [FileSpec; 4] = [
+		FileSpec { dir: b'$', name: b"!BOOT", load_addr: 0, exec_addr: 0,
+			locked: false, content: b"CHAIN \"MENU\"\r" },
+		FileSpec { dir: b'$', name: b"MENU", load_addr: 0x1900, exec_addr: 0x8023,
+			locked: true, content: &[0x0d; 300] },
+		FileSpec { dir: b'B', name: b"EMPTY", load_addr: 0x3ffff, exec_addr: 0x3ffff,
+			locked: false, content: b"" },
+		FileSpec { dir: b'Z', name: b"LOADER", load_addr: 0x11900, exec_addr: 0x21900,
+			locked: true, content: &[0xaa; 512] },
+	];
+
+	fn check(spec: &DiscSpec) {
+		let image = make_image(spec);
+		let disc = dfs::Disc::from_bytes(&image)
+			.unwrap_or_else(|e| panic!("{:?} failed to parse: {:?}", spec, e));
+
+		let name_len = spec.name.iter().take_while(|&&b| b > b' ').count();
+		assert_eq!(&spec.name[..name_len], disc.name().as_bytes());
+		assert_eq!(spec.cycle, disc.cycle().into_u8());
+		assert_eq!(spec.boot_option, disc.boot_option());
+		assert_eq!(spec.files.len(), disc.files().count());
+
+		for f in spec.files {
+			let name = dfs::FileName::try_from(f.name).unwrap();
+			let dir = crate::support::AsciiPrintingChar::from(f.dir).unwrap();
+			let file = disc.find_file(&name, dir).expect("file missing");
+			assert_eq!(f.load_addr, file.load_addr());
+			assert_eq!(f.exec_addr, file.exec_addr());
+			assert_eq!(f.locked, file.is_locked());
+			assert_eq!(f.content, file.content());
+		}
+	}
+
+	#[test]
+	fn parse_make_image() {
+		let boot_options = [BootOption::None, BootOption::Load, BootOption::Run, BootOption::Exec];
+		let names: [&[u8]; 4] = [b"", b"A", b"EIGHTCHR", b"TWELVECHARS!"];
+
+		for (i, &boot_option) in boot_options.iter().enumerate() {
+			for (j, &name) in names.iter().enumerate() {
+				for n in 0..=FILES.len() {
+					check(&DiscSpec {
+						name,
+						cycle: (i * 31 + j * 7 + n) as u8 % 100,
+						boot_option,
+						total_sectors: if n % 2 == 0 { 400 } else { 800 },
+						files: &FILES[..n],
+					});
+				}
+			}
+		}
+	}
+}
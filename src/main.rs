@@ -11,7 +11,11 @@ use std::str::FromStr;
 
 use gumdrop::Options;
 
-const XML_NAMESPACE: &'static str = "http://pearfalse.com/schemas/2021/dfs-manifest";
+mod base64;
+#[cfg(feature = "zip")]
+mod zip;
+
+const XML_NAMESPACE: &str = "http://pearfalse.com/schemas/2021/dfs-manifest";
 
 #[derive(Debug, Options)]
 struct CliArgs {
@@ -26,10 +30,66 @@ struct CliArgs {
 enum Subcommand {
 	#[options(help = "dump the contents of a disc image")]
 	Probe(ScProbe),
+	#[options(help = "list the files on a disc image")]
+	Ls(ScLs),
 	#[options(help = "build a disc image from source files and a manifest")]
 	Pack(ScPack),
 	#[options(help = "unpack a disc image into separate files (and a manifest)")]
 	Unpack(ScUnpack),
+	#[options(help = "add a file to an existing disc image")]
+	Add(ScAdd),
+	#[options(help = "remove a file from an existing disc image")]
+	Remove(ScRemove),
+	#[options(help = "change the title of a disc image")]
+	Title(ScTitle),
+	#[options(help = "create a blank, formatted disc image")]
+	Create(ScCreate),
+	#[options(help = "sort the catalogue of a disc image")]
+	Sort(ScSort),
+	#[options(help = "list the differences between two disc images")]
+	Diff(ScDiff),
+	#[options(help = "check a disc image for damage")]
+	Verify(ScVerify),
+	#[options(help = "join two single-sided images into a double-sided one")]
+	Join(ScJoin),
+	#[options(help = "split a double-sided image into two single-sided ones")]
+	Split(ScSplit),
+}
+
+#[derive(Debug, Options)]
+struct ScJoin {
+	#[options()]
+	help: bool,
+
+	#[options(help = "overwrite the output image if it already exists")]
+	force: bool,
+
+	#[options(free, required, help = "image for side 0")]
+	side0: OsString,
+
+	#[options(free, required, help = "image for side 1")]
+	side1: OsString,
+
+	#[options(free, required, help = "double-sided image to write")]
+	output: OsString,
+}
+
+#[derive(Debug, Options)]
+struct ScSplit {
+	#[options()]
+	help: bool,
+
+	#[options(help = "overwrite the output images if they already exist")]
+	force: bool,
+
+	#[options(free, required, help = "double-sided image to split")]
+	image_file: OsString,
+
+	#[options(free, required, help = "image to write side 0 to")]
+	side0: OsString,
+
+	#[options(free, required, help = "image to write side 1 to")]
+	side1: OsString,
 }
 
 #[derive(Debug, Options)]
@@ -37,10 +97,47 @@ struct ScProbe {
 	#[options()]
 	help: bool,
 
+	#[options(no_short, help = "print a hexdump of one file instead, e.g. $.FOO")]
+	hexdump: Option<String>,
+
+	#[options(no_short, meta = "N[-M]",
+		help = "hexdump sectors N to M of the raw image, even if it won't parse",
+		parse(try_from_str = "parse_sector_range"))]
+	dump_sectors: Option<SectorRange>,
+
+	#[options(no_short, help = "print the disc's details as JSON")]
+	json: bool,
+
+	#[options(no_short, help = "print a map of which sectors each file uses")]
+	map: bool,
+
+	#[options(no_short, meta = "STYLE", help = "how to write addresses and lengths (acorn, c or dec)",
+		parse(try_from_str = "parse_addr_format"))]
+	addr_format: Option<AddrFormat>,
+
 	#[options(free)]
 	image_file: OsString,
 }
 
+#[derive(Debug, Options)]
+struct ScLs {
+	#[options()]
+	help: bool,
+
+	#[options(help = "also show addresses, lengths and locked files")]
+	long: bool,
+
+	#[options(help = "only list files in this directory", parse(try_from_str = "parse_dfs_dir"))]
+	dir: Option<AsciiPrintingChar>,
+
+	#[options(no_short, meta = "STYLE", help = "how to write addresses and lengths (acorn, c or dec)",
+		parse(try_from_str = "parse_addr_format"))]
+	addr_format: Option<AddrFormat>,
+
+	#[options(free, required, help = "disc image to list")]
+	image_file: OsString,
+}
+
 #[derive(Debug, Options)]
 struct ScPack {
 	#[options()]
@@ -49,6 +146,16 @@ struct ScPack {
 	#[options(short = "x", long = "manifest")]
 	manifest: OsString,
 
+	#[options(no_short, help = "accept manifests without an XML namespace quietly")]
+	lenient: bool,
+
+	#[options(no_short, help = "also add every file in this host folder to $")]
+	add_dir: Option<OsString>,
+
+	#[options(no_short, help = "for --add-dir, what to do with clashing names: error, numeric-suffix or skip",
+		parse(try_from_str = "parse_collision_policy"), default = "error")]
+	collisions: dfs::CollisionPolicy,
+
 	#[options(free)]
 	output_file: OsString,
 }
@@ -61,16 +168,170 @@ struct ScUnpack {
 	#[options(short = "o", long = "output", help = "output folder")]
 	output: OsString,
 
+	#[options(no_short, help = "put file contents in the manifest, as base64")]
+	inline: bool,
+
+	#[options(no_short, meta = "STYLE",
+		help = "convert line endings in text files (lf, crlf or cr); these won't pack back byte-for-byte",
+		parse(try_from_str = "parse_newline_style"))]
+	newline: Option<dfs::NewlineStyle>,
+
+	#[options(no_short, meta = "KEY",
+		help = "order of files in the manifest: directory (default), name, start-sector, load-addr or catalogue-index",
+		parse(try_from_str = "parse_sort_key"), default = "directory")]
+	sort: dfs::SortKey,
+
 	#[options(free)]
 	image_file: OsString,
 }
 
+#[derive(Debug, Options)]
+struct ScAdd {
+	#[options()]
+	help: bool,
+
+	#[options(help = "load address, in hex", parse(try_from_str = "parse_hex_addr"))]
+	load: Option<u32>,
+
+	#[options(help = "execution address, in hex", parse(try_from_str = "parse_hex_addr"))]
+	exec: Option<u32>,
+
+	#[options(no_short, help = "mark the file as locked")]
+	locked: bool,
+
+	#[options(help = "replace a file with the same name, even if it is locked")]
+	force: bool,
+
+	#[options(no_short, help = "don't bump the disc's cycle count")]
+	no_increment_cycle: bool,
+
+	#[options(free, required, help = "disc image to modify")]
+	image_file: OsString,
+
+	#[options(free, required, help = "DFS name of the file, e.g. $.FOO")]
+	file_name: String,
+
+	#[options(free, required, help = "host file to add")]
+	source: OsString,
+}
+
+#[derive(Debug, Options)]
+struct ScRemove {
+	#[options()]
+	help: bool,
+
+	#[options(no_short, help = "only print what would be removed")]
+	dry_run: bool,
+
+	#[options(help = "remove locked files too")]
+	force: bool,
+
+	#[options(no_short, help = "don't bump the disc's cycle count")]
+	no_increment_cycle: bool,
+
+	#[options(free, required, help = "disc image to modify")]
+	image_file: OsString,
+
+	#[options(free, required, help = "DFS name of the file, e.g. $.FOO, or a pattern such as B.*")]
+	file_name: String,
+}
+
+#[derive(Debug, Options)]
+struct ScTitle {
+	#[options()]
+	help: bool,
+
+	#[options(no_short, help = "don't bump the disc's cycle count")]
+	no_increment_cycle: bool,
+
+	#[options(free, required, help = "disc image to modify")]
+	image_file: OsString,
+
+	#[options(free, required, help = "new title, up to 12 characters")]
+	title: String,
+}
+
+#[derive(Debug, Options)]
+struct ScCreate {
+	#[options()]
+	help: bool,
+
+	#[options(help = "disc title, up to 12 characters")]
+	name: Option<String>,
+
+	#[options(help = "tracks per side (40 or 80)", default = "40")]
+	tracks: u16,
+
+	#[options(help = "sides (1 or 2); two sides make an interleaved image", default = "1")]
+	sides: u8,
+
+	#[options(help = "boot option (none, load, run or exec, or 0-3)",
+		parse(try_from_str = "parse_boot_option"))]
+	boot: Option<dfs::BootOption>,
+
+	#[options(help = "overwrite the image if it already exists")]
+	force: bool,
+
+	#[options(free, required, help = "disc image to create")]
+	image_file: OsString,
+}
+
+#[derive(Debug, Options)]
+struct ScSort {
+	#[options()]
+	help: bool,
+
+	#[options(help = "name, directory, start-sector, load-addr or catalogue-index",
+		parse(try_from_str = "parse_sort_key"), default = "directory")]
+	by: dfs::SortKey,
+
+	#[options(no_short, help = "don't bump the disc's cycle count")]
+	no_increment_cycle: bool,
+
+	#[options(free, required, help = "disc image to modify")]
+	image_file: OsString,
+}
+
+#[derive(Debug, Options)]
+struct ScDiff {
+	#[options()]
+	help: bool,
+
+	#[options(free, required, help = "original disc image")]
+	old_image: OsString,
+
+	#[options(free, required, help = "changed disc image")]
+	new_image: OsString,
+}
+
+#[derive(Debug, Options)]
+struct ScVerify {
+	#[options()]
+	help: bool,
+
+	#[options(no_short, help = "also reject unaddressable file names, and garbage after names")]
+	strict: bool,
+
+	#[options(free, required, help = "disc image to check")]
+	image_file: OsString,
+}
+
 fn main() {
 	let args = CliArgs::parse_args_default_or_exit();
 	let r = match args.command {
-		Some(Subcommand::Probe(ref probe)) => sc_probe(&*probe.image_file),
-		Some(Subcommand::Unpack(ref unpack)) => sc_unpack(&*unpack.image_file, &*unpack.output),
-		Some(Subcommand::Pack(ref pack)) => sc_pack(pack.manifest.as_ref(), pack.output_file.as_ref()),
+		Some(Subcommand::Probe(ref probe)) => sc_probe(probe),
+		Some(Subcommand::Ls(ref ls)) => sc_ls(ls),
+		Some(Subcommand::Unpack(ref unpack)) => sc_unpack(&unpack.image_file, &unpack.output, unpack.inline, unpack.newline, unpack.sort),
+		Some(Subcommand::Pack(ref pack)) => sc_pack(pack),
+		Some(Subcommand::Add(ref add)) => sc_add(add),
+		Some(Subcommand::Remove(ref remove)) => sc_remove(remove),
+		Some(Subcommand::Title(ref title)) => sc_title(title),
+		Some(Subcommand::Create(ref create)) => sc_create(create),
+		Some(Subcommand::Sort(ref sort)) => sc_sort(sort),
+		Some(Subcommand::Diff(ref diff)) => sc_diff(diff),
+		Some(Subcommand::Verify(ref verify)) => sc_verify(verify),
+		Some(Subcommand::Join(ref join)) => sc_join(join),
+		Some(Subcommand::Split(ref split)) => sc_split(split),
 		None => {
 			eprintln!("{}", args.self_usage());
 			std::process::exit(1);
@@ -78,7 +339,8 @@ fn main() {
 	};
 
 	if let Err(e) = r {
-		eprintln!("{:?}", e);
+		eprintln!("error: {}", e);
+		std::process::exit(1);
 	}
 }
 
@@ -89,6 +351,23 @@ enum CliError {
 	BadImage(dfs::DFSError),
 	XmlParseError(xml::reader::Error),
 	ManifestError(Cow<'static, str>),
+	ArgumentError(Cow<'static, str>),
+	#[cfg(feature = "zip")]
+	ArchiveError(Cow<'static, str>),
+}
+
+impl std::fmt::Display for CliError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InputTooLarge => f.write_str("input is too large to be a disc image"),
+			Self::Io(e) => e.fmt(f),
+			Self::BadImage(e) => e.fmt(f),
+			Self::XmlParseError(e) => write!(f, "manifest is not valid XML: {}", e),
+			Self::ManifestError(s) | Self::ArgumentError(s) => f.write_str(s),
+			#[cfg(feature = "zip")]
+			Self::ArchiveError(s) => f.write_str(s),
+		}
+	}
 }
 
 impl<O> From<CliError> for Result<O, CliError> {
@@ -124,43 +403,333 @@ macro_rules! warn {
 }
 
 
+/// Parses a disc image, saying so plainly if it isn't DFS at all.
+fn open_disc(image_data: &[u8]) -> Result<dfs::Disc<'_>, CliError> {
+	dfs::Disc::from_bytes(image_data).map_err(|e| match dfs::looks_like_dfs(image_data) {
+		true => CliError::BadImage(e),
+		false => not_dfs(),
+	})
+}
+
+fn not_dfs() -> CliError {
+	CliError::ArgumentError(Cow::Borrowed("this doesn't look like a DFS image"))
+}
+
 fn read_image(path: &OsStr) -> Result<Vec<u8>, CliError> {
+	read_image_unzipped(path).map(|(data, _)| data)
+}
+
+/// Reads an image that is going to be written back to `path`. An image
+/// taken out of a ZIP archive is refused, as writing it back would replace
+/// the whole archive with it.
+fn read_image_for_update(path: &OsStr) -> Result<Vec<u8>, CliError> {
+	match read_image_unzipped(path)? {
+		(_, true) => Err(CliError::ArgumentError(Cow::Owned(format!(
+			"'{}' is a ZIP archive; extract the disc image from it to change it",
+			Path::new(path).display()
+		)))),
+		(data, false) => Ok(data),
+	}
+}
+
+/// Reads a whole image, refusing anything over `MAX_DISC_SIZE` bytes.
+///
+/// With the `zip` feature, a ZIP archive is read instead as its first
+/// `.ssd` or `.dsd` member. The flag returned says whether this happened.
+fn read_image_unzipped(path: &OsStr) -> Result<(Vec<u8>, bool), CliError> {
 	let mut data = Vec::new();
 
 	if path == "-" {
 		let stdin = io::stdin();
-		stdin.lock().read_to_end(&mut data)
+		stdin.lock().take(dfs::MAX_DISC_SIZE + 1).read_to_end(&mut data)
 			.map_err(CliError::Io)?;
+		if data.len() as u64 > dfs::MAX_DISC_SIZE {
+			return Err(CliError::InputTooLarge);
+		}
 	} else {
 		File::open(path).map_err(CliError::Io)
 		.and_then(|mut f| {
 			let file_len = f.metadata().map_err(CliError::Io)?.len();
-			if file_len > dfs::MAX_DISC_SIZE as u64 {
+			if file_len > dfs::MAX_DISC_SIZE {
 				return Err(CliError::InputTooLarge);
 			}
 			f.read_to_end(&mut data).map_err(CliError::Io)
 		})?;
 	}
 
-	Ok(data)
+	#[cfg(feature = "zip")]
+	if zip::is_zip(&data) {
+		return zip::extract_disc_image(&data, dfs::MAX_DISC_SIZE as usize)
+			.map(|image| (image, true))
+			.map_err(CliError::ArchiveError);
+	}
+
+	Ok((data, false))
 }
 
+fn write_image(path: &OsStr, data: &[u8]) -> CliResult {
+	use std::io::Write;
 
-fn sc_probe(image_path: &OsStr) -> Result<(), CliError> {
-	let image_data = read_image(image_path)?;
+	if path == "-" {
+		io::stdout().lock().write_all(data)?;
+	} else {
+		File::create(path).and_then(|mut f| f.write_all(data))?;
+	}
+
+	Ok(())
+}
+
+fn parse_hex_addr(src: &str) -> Result<u32, std::num::ParseIntError> {
+	u32::from_str_radix(src, 16)
+}
+
+/// An inclusive range of sectors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct SectorRange {
+	first: u32,
+	last: u32,
+}
+
+/// Parses a sector or an inclusive range of sectors, such as `2-5`.
+fn parse_sector_range(src: &str) -> Result<SectorRange, String> {
+	let bad = || format!("'{}' is not a sector range", src);
+	let (first, last) = src.split_once('-').unwrap_or((src, src));
+	let first = u32::from_str(first.trim()).map_err(|_| bad())?;
+	let last = u32::from_str(last.trim()).map_err(|_| bad())?;
+	match first <= last {
+		true => Ok(SectorRange { first, last }),
+		false => Err(bad()),
+	}
+}
 
-	let disc = dfs::Disc::from_bytes(&image_data)
-		.map_err(CliError::BadImage)?;
+fn parse_boot_option(src: &str) -> Result<dfs::BootOption, String> {
+	dfs::BootOption::from_opt4_arg(src)
+		.map_err(|_| format!("'{}' is not a boot option", src))
+}
+
+fn parse_collision_policy(src: &str) -> Result<dfs::CollisionPolicy, String> {
+	dfs::CollisionPolicy::from_str(src)
+		.map_err(|_| format!("'{}' is not a collision policy", src))
+}
+
+fn parse_newline_style(src: &str) -> Result<dfs::NewlineStyle, String> {
+	dfs::NewlineStyle::from_str(src)
+		.map_err(|_| format!("'{}' is not a newline style", src))
+}
+
+fn parse_addr_format(src: &str) -> Result<AddrFormat, String> {
+	AddrFormat::from_str(src)
+		.map_err(|_| format!("'{}' is not an address format", src))
+}
+
+fn parse_dfs_dir(src: &str) -> Result<AsciiPrintingChar, String> {
+	AsciiPrintingChar::try_from_str(src)
+		.map_err(|_| format!("'{}' is not a directory", src))
+}
+
+fn parse_sort_key(src: &str) -> Result<dfs::SortKey, String> {
+	dfs::SortKey::from_str(src)
+		.map_err(|_| format!("'{}' is not a sort order", src))
+}
+
+/// Splits a DFS path like `$.FOO` into its directory and file name. A path
+/// without a directory prefix refers to `$`.
+fn parse_dfs_path(src: &str) -> Result<(AsciiPrintingChar, dfs::FileName), CliError> {
+	let (dir, name) = match src.as_bytes() {
+		[_, b'.', ..] => (&src[..1], &src[2..]),
+		_ => ("$", src),
+	};
+
+	let dir = AsciiPrintingChar::try_from_str(dir)
+		.map_err(|_| CliError::ArgumentError(Cow::Borrowed("directory is not a printing ascii char")))?;
+	if !dir.is_valid_dfs_dir() {
+		return Err(CliError::ArgumentError(Cow::Owned(format!(
+			"'{}' is reserved by DFS and can't be a directory", dir
+		))));
+	}
+	let name = dfs::FileName::try_from(name.as_bytes())
+		.map_err(|e| CliError::ArgumentError(Cow::Owned(format!(
+			"file name has an invalid character at position {}", e.position()
+		))))?;
+
+	Ok((dir, name))
+}
+
+
+fn sc_probe(args: &ScProbe) -> Result<(), CliError> {
+	let image_data = read_image(&args.image_file)?;
+
+	if let Some(SectorRange { first, last }) = args.dump_sectors {
+		let sectors_in_image = image_data.len().sectors() as u32;
+		if last >= sectors_in_image {
+			warn!("the image ends at sector {}; sectors past that aren't there",
+				sectors_in_image.saturating_sub(1));
+		}
+		print!("{}", dump_sectors(&image_data, first, last));
+		return Ok(());
+	}
+
+	let disc = open_disc(&image_data)?;
+
+	if let Some(ref path) = args.hexdump {
+		let (dir, name) = parse_dfs_path(path)?;
+		let file = disc.find_file(&name, dir)
+			.ok_or_else(|| CliError::ArgumentError(Cow::Owned(format!(
+				"file '{}.{}' not found", dir, name
+			))))?;
+		print!("{}", hexdump(file.content(), file.load_addr()));
+		return Ok(());
+	}
+
+	if args.json {
+		println!("{}", probe_json(&disc));
+		return Ok(());
+	}
+
+	if args.map {
+		print!("{}", sector_map(&disc));
+		return Ok(());
+	}
 
 	println!("Opened disc {}", disc.name());
 	println!("Files:");
 	for file in disc.files() {
-		println!("{}", file);
+		match args.addr_format {
+			Some(style) => println!("{}.{} (load {}, exec {}, size {})", file.dir(), file.name(),
+				style.format(file.load_addr()), style.format(file.exec_addr()),
+				style.format(file.content().len() as u32)),
+			None => println!("{}", file),
+		}
 	}
 	Ok(())
 }
 
-fn sc_unpack(image_path: &OsStr, target: &OsStr) -> CliResult {
+/// Hexdumps sectors `first` to `last` of an image, addressed by their
+/// offset into it. Sectors past the end of the image are left out.
+fn dump_sectors(image: &[u8], first: u32, last: u32) -> String {
+	let start = (first as usize).saturating_mul(dfs::SECTOR_SIZE).min(image.len());
+	let end = (last as usize + 1).saturating_mul(dfs::SECTOR_SIZE).min(image.len());
+	hexdump(&image[start..end], start as u32).to_string()
+}
+
+/// How addresses are written in listings.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, enum_utils::FromStr)]
+#[enumeration(case_insensitive, rename_all = "kebab-case")]
+enum AddrFormat {
+	/// `&1900`, as Acorn tools write it.
+	Acorn,
+	/// `0x1900`.
+	C,
+	/// `6400`.
+	Dec,
+}
+
+impl AddrFormat {
+	fn format(self, n: u32) -> String {
+		match self {
+			Self::Acorn => dfsdisc::support::fmt_acorn_addr(n),
+			Self::C     => format!("0x{:x}", n),
+			Self::Dec   => n.to_string(),
+		}
+	}
+}
+
+fn sc_ls(args: &ScLs) -> CliResult {
+	let image_data = read_image(&args.image_file)?;
+	let disc = open_disc(&image_data)?;
+	print!("{}", list_files(&disc, args.dir, args.long, args.addr_format));
+	Ok(())
+}
+
+/// Lists files one per line, sorted by directory then name as `*CAT`
+/// does. The long form adds load and execution addresses, length, and `L`
+/// for locked files. These are six hex digits unless `addr_format` is given.
+fn list_files(disc: &dfs::Disc, dir: Option<AsciiPrintingChar>, long: bool,
+	addr_format: Option<AddrFormat>) -> String {
+	let mut out = String::new();
+	for file in disc.files_sorted(dfs::SortKey::Directory) {
+		if dir.is_some_and(|d| d != file.dir()) {
+			continue;
+		}
+		let path = format!("{}.{}", file.dir(), file.name());
+		let lock = if file.is_locked() { " L" } else { "" };
+		match (long, addr_format) {
+			(true, None) => out.push_str(&format!("{:<9} {:06x} {:06x} {:06x}{}\n", path,
+				file.load_addr(), file.exec_addr(), file.content().len(), lock)),
+			(true, Some(style)) => out.push_str(&format!("{:<9} {:<7} {:<7} {}{}\n", path,
+				style.format(file.load_addr()), style.format(file.exec_addr()),
+				style.format(file.content().len() as u32), lock)),
+			(false, _) => { out.push_str(&path); out.push('\n'); },
+		}
+	}
+	out
+}
+
+/// Draws a disc's sector map, one track (10 sectors) per row. The catalogue
+/// is drawn as `#`, free sectors as `.`, and each file as a symbol given in
+/// the key below the map.
+fn sector_map(disc: &dfs::Disc) -> String {
+	const SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+	let mut out = String::new();
+	let mut key: Vec<(dfs::FileId, char)> = Vec::new();
+	for (i, state) in disc.sector_map().into_iter().enumerate() {
+		if i % 10 == 0 {
+			if i > 0 { out.push('\n'); }
+			out.push_str(&format!("{:3} ", i / 10));
+		}
+		out.push(match state {
+			dfs::SectorState::Catalogue => '#',
+			dfs::SectorState::Free => '.',
+			dfs::SectorState::File(id) => match key.iter().find(|(k, _)| *k == id) {
+				Some(&(_, c)) => c,
+				None => {
+					let c = SYMBOLS[key.len() % SYMBOLS.len()] as char;
+					key.push((id, c));
+					c
+				},
+			},
+		});
+	}
+	out.push('\n');
+
+	for (id, c) in key {
+		out.push_str(&format!("{} {}\n", c, id));
+	}
+	out
+}
+
+/// Describes a disc as a JSON object. Addresses are hex strings, as in the
+/// manifest; the cycle count is a decimal string.
+fn probe_json(disc: &dfs::Disc) -> String {
+	let files: Vec<String> = disc.files().map(|file| format!(
+		"{{\"dir\":{},\"name\":{},\"load\":\"{:04x}\",\"exec\":\"{:04x}\",\"length\":{},\"locked\":{}}}",
+		json_string(file.dir().as_ascii_str().as_str()), json_string(file.name().as_str()),
+		file.load_addr(), file.exec_addr(), file.content().len(), file.is_locked()
+	)).collect();
+
+	format!("{{\"name\":{},\"cycle\":\"{}\",\"boot\":\"{}\",\"tracks\":{},\"sides\":1,\"files\":[{}]}}",
+		json_string(disc.name().as_str()), disc.cycle_display(), disc.boot_option().as_str(),
+		disc.geometry().tracks(), files.join(","))
+}
+
+/// Quotes `src` as a JSON string.
+fn json_string(src: &str) -> String {
+	let mut quoted = String::with_capacity(src.len() + 2);
+	quoted.push('"');
+	for c in src.chars() {
+		match c {
+			'"' | '\\' => { quoted.push('\\'); quoted.push(c); },
+			c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+			c => quoted.push(c),
+		}
+	}
+	quoted.push('"');
+	quoted
+}
+
+fn sc_unpack(image_path: &OsStr, target: &OsStr, inline: bool,
+	newline: Option<dfs::NewlineStyle>, sort: dfs::SortKey) -> CliResult {
 	use std::fs;
 	use std::io::Write;
 	use ascii::{AsciiChar,AsciiStr};
@@ -171,44 +740,46 @@ fn sc_unpack(image_path: &OsStr, target: &OsStr) -> CliResult {
 		namespace::Namespace,
 	};
 
-	const SEPARATOR: AsciiChar = AsciiChar::Slash;
+	const SEPARATOR: AsciiChar = dfsdisc::support::AsciiPrintingChar::SLASH.as_ascii_char();
 	let root_namespace = Namespace({
 		let mut map = std::collections::BTreeMap::new();
 		map.insert(String::from(xml::namespace::NS_NO_PREFIX), String::from(XML_NAMESPACE));
 		map
 	});
 
+	let target = Path::new(target);
 	fs::DirBuilder::new()
 		.recursive(true)
 		.create(target)
 		?;
 
-	std::env::set_current_dir(target)?;
-
 	let image_data = read_image(image_path)?;
-	let disc = dfs::Disc::from_bytes(&image_data)?;
+	let disc = open_disc(&image_data)?;
 
-	let dirs: std::collections::HashSet<dfsdisc::support::AsciiPrintingChar>
-		= disc.files().map(|f| f.dir()).collect();
+	let dirs: std::collections::BTreeSet<dfsdisc::support::AsciiPrintingChar>
+		= disc.files().filter(|_| !inline).map(|f| f.dir()).collect();
 
 	for dir in dirs {
-		std::fs::create_dir_all(dir.as_ascii_str().as_str())?;
+		std::fs::create_dir_all(target.join(dir.as_ascii_str().as_str()))?;
 	}
 
 	let mut file_path_buf = arrayvec::ArrayVec::<AsciiChar, 9>::new(); // 9 == 7 of file + dir + SEPARATOR
-	for file in disc.files() {
+	for file in disc.files().filter(|_| !inline) {
 		file_path_buf.clear();
 		file_path_buf.push(*file.dir());
 		file_path_buf.push(SEPARATOR);
 		file_path_buf.extend(file.name().as_slice().iter().copied());
 
-		fs::File::create(<&AsciiStr>::from(&*file_path_buf).as_str())
-			.and_then(|mut f| f.write_all(file.content()))
-			?;
+		let path = target.join(<&AsciiStr>::from(&*file_path_buf).as_str());
+		match newline {
+			Some(style) if element_name(file) == "text" =>
+				fs::write(path, file.as_text(style).as_bytes())?,
+			_ => fs::write(path, file.content())?,
+		}
 	}
 
 	// create manifest file
-	let mut manifest = fs::File::create("manifest.xml")
+	let mut manifest = fs::File::create(target.join("manifest.xml"))
 		.map(|f| xml::writer::EventWriter::new_with_config(f, xml::writer::EmitterConfig {
 			indent_string: Cow::Borrowed("\t"),
 			perform_indent: true,
@@ -225,15 +796,22 @@ fn sc_unpack(image_path: &OsStr, target: &OsStr) -> CliResult {
 		})?;
 
 		// <dfsdisc>
-		let attr_cycle = format!("{}", disc.cycle().into_u8());
-		let start_attrs = [
+		let attr_cycle = disc.cycle_display();
+		let attr_tracks = disc.track_count().to_string();
+		// only a note for the reader; pack finds the boot file by name
+		let attr_boot_file = disc.boot_file()
+			.filter(|_| disc.boot_option() != dfs::BootOption::None)
+			.map(|f| format!("{}.{}", f.dir(), f.name()));
+		let mut start_attrs = vec![
 			Attribute::new(XmlName::local("name"), disc.name().as_str()),
-			// hardcoding to 100KiB 40T DFS for now. TODO fix this, obviously
 			Attribute::new(XmlName::local("sides"), "1"),
-			Attribute::new(XmlName::local("tracks"), "40"),
+			Attribute::new(XmlName::local("tracks"), &attr_tracks),
 			Attribute::new(XmlName::local("cycle"), &attr_cycle),
 			Attribute::new(XmlName::local("boot"), disc.boot_option().as_str()),
 		];
+		if let Some(ref boot_file) = attr_boot_file {
+			start_attrs.push(Attribute::new(XmlName::local("boot-file"), boot_file));
+		}
 		manifest.write(XmlEvent::StartElement {
 			name: XmlName::local("dfsdisc"),
 			attributes: Cow::Borrowed(&start_attrs[..]),
@@ -241,30 +819,36 @@ fn sc_unpack(image_path: &OsStr, target: &OsStr) -> CliResult {
 		})?;
 
 		let ns_empty = xml::namespace::Namespace::empty();
-		for file in disc.files() {
-			let element_name = match file.exec_addr() & 0xffff {
-				0x801f | 0x8023 if file.content().looks_like_basic() => "basic",
-				0xffff if file.content().is_mos_text() => "text",
-				n if n >= 0x900 && n < 0x8000 => "code",
-				_ => "data"
-			};
+		for file in disc.files_sorted(sort) {
+			let element_name = element_name(file);
 
 			let dir1 = [file.dir().as_ascii_char()];
 			let load_str = format!("{:04x}", file.load_addr());
 			let exec_str = format!("{:04x}", file.exec_addr());
+			let length_str = format!("{:x}", file.content().len());
 
 			file_path_buf.clear();
 			file_path_buf.push(dir1[0]);
 			file_path_buf.push(SEPARATOR);
 			file_path_buf.extend(file.name().as_slice().iter().copied());
 
-			let file_attrs = [
+			let mut file_attrs = vec![
 				Attribute::new(XmlName::local("name"), file.name().as_str()),
 				Attribute::new(XmlName::local("dir"), <&AsciiStr>::from(&dir1[..]).as_str()),
-				Attribute::new(XmlName::local("src"), <&AsciiStr>::from(&*file_path_buf).as_str()),
-				Attribute::new(XmlName::local("load"), &*load_str),
-				Attribute::new(XmlName::local("exec"), &*exec_str),
+				Attribute::new(XmlName::local("load"), &load_str),
+				Attribute::new(XmlName::local("exec"), &exec_str),
 			];
+			// a converted text file has a different length on the host
+			if inline || newline.is_none() || element_name != "text" {
+				file_attrs.push(Attribute::new(XmlName::local("length"), &length_str));
+			}
+			if file.is_locked() {
+				file_attrs.push(Attribute::new(XmlName::local("locked"), "true"));
+			}
+			if !inline {
+				file_attrs.insert(2,
+					Attribute::new(XmlName::local("src"), <&AsciiStr>::from(&*file_path_buf).as_str()));
+			}
 
 			// <[code|data|text]/>
 			manifest.write(XmlEvent::StartElement {
@@ -272,6 +856,9 @@ fn sc_unpack(image_path: &OsStr, target: &OsStr) -> CliResult {
 				attributes: Cow::Borrowed(&file_attrs[..]),
 				namespace: Cow::Borrowed(&ns_empty),
 			})?;
+			if inline {
+				manifest.write(XmlEvent::characters(&base64::encode(file.content())))?;
+			}
 			manifest.write(XmlEvent::end_element())?;
 		}
 
@@ -289,6 +876,320 @@ fn sc_unpack(image_path: &OsStr, target: &OsStr) -> CliResult {
 	Ok(())
 }
 
+/// The manifest element for a file, guessed from its addresses and content.
+fn element_name(file: &dfs::File) -> &'static str {
+	match file.exec_addr() & 0xffff {
+		0x801f | 0x8023 if file.content().looks_like_basic() => "basic",
+		_ if file.is_relocatable() && file.content().is_mos_text() => "text",
+		0x900..=0x7fff => "code",
+		_ => "data"
+	}
+}
+
+fn sc_add(args: &ScAdd) -> CliResult {
+	let (dir, name) = parse_dfs_path(&args.file_name)?;
+	if !name.is_addressable() {
+		return Err(CliError::ArgumentError(Cow::Owned(format!(
+			"file name '{}' contains characters reserved by DFS", name
+		))));
+	}
+
+	let contents = {
+		let mut src = File::open(&args.source)?;
+		if src.metadata()?.len() > dfs::MAX_DISC_SIZE {
+			return Err(CliError::InputTooLarge);
+		}
+		let mut c = Vec::new();
+		src.read_to_end(&mut c)?;
+		c
+	};
+
+	let image_data = read_image_for_update(&args.image_file)?;
+	let mut disc = open_disc(&image_data)?;
+
+	if !args.force && disc.find_file(&name, dir).is_some() {
+		return Err(CliError::ArgumentError(Cow::Owned(format!(
+			"file '{}.{}' already exists; use --force to replace it", dir, name
+		))));
+	}
+
+	let file = dfs::File::new(name, dir,
+		args.load.unwrap_or(0), args.exec.unwrap_or(0), args.locked,
+		Cow::Owned(contents));
+	match disc.force_add_file(file) {
+		Ok(None) => {},
+		Ok(Some(old)) => warn!("replacing existing file '{}.{}'", old.dir(), old.name()),
+		Err((e, failed)) => return Err(CliError::ArgumentError(Cow::Owned(format!(
+			"{}; can't add '{}.{}'", e, failed.dir(), failed.name()
+		)))),
+	};
+
+	disc.set_auto_increment_cycle(!args.no_increment_cycle);
+	let new_image = disc.to_vec()?;
+	drop(disc);
+
+	write_image(&args.image_file, &new_image)
+}
+
+/// Removes a file, or every file matching a wildcard pattern, from an
+/// image. The image is rewritten with the remaining files packed together,
+/// so the removed files' data does not survive.
+fn sc_remove(args: &ScRemove) -> CliResult {
+	let image_data = read_image_for_update(&args.image_file)?;
+	let mut disc = open_disc(&image_data)?;
+
+	let targets: Vec<(AsciiPrintingChar, dfs::FileName)> = if dfs::glob::is_pattern(&args.file_name) {
+		disc.files_matching(&args.file_name)
+			.map(|f| (f.dir(), dfs::FileName::try_from(f.name().as_bytes()).unwrap()))
+			.collect()
+	} else {
+		vec![parse_dfs_path(&args.file_name)?]
+	};
+
+	let mut removed = Vec::with_capacity(targets.len());
+	for (dir, name) in targets {
+		let file = match args.force {
+			true => disc.remove_file(&name, dir),
+			false => disc.remove_file_checked(&name, dir)?,
+		};
+		removed.push(file
+			.ok_or_else(|| CliError::ArgumentError(Cow::Owned(format!(
+				"file '{}.{}' not found", dir, name
+			))))?);
+	}
+	if removed.is_empty() {
+		return Err(CliError::ArgumentError(Cow::Owned(format!(
+			"no files match '{}'", args.file_name
+		))));
+	}
+
+	if args.dry_run {
+		for file in &removed {
+			println!("would remove {}", file);
+		}
+		return Ok(());
+	}
+
+	disc.set_auto_increment_cycle(!args.no_increment_cycle);
+	let new_image = disc.to_vec()?;
+	drop(disc);
+
+	write_image(&args.image_file, &new_image)
+}
+
+/// Changes the title of an image and bumps its cycle count, unless asked not
+/// to. Only the title and cycle bytes in the catalogue are rewritten; file
+/// data is untouched.
+fn sc_title(args: &ScTitle) -> CliResult {
+	let new_title = AsciiPrintingStr::try_from_str(&args.title)
+		.map_err(|_| CliError::ArgumentError(Cow::Borrowed(
+			"disc title has non-printing or non-ASCII characters"
+		)))?;
+	if new_title.len() > 12 {
+		warn!("disc title is longer than 12 characters; truncating");
+	}
+
+	let mut image_data = read_image_for_update(&args.image_file)?;
+
+	let (title, cycle) = {
+		let mut disc = open_disc(&image_data)?;
+		disc.set_name(new_title.up_to(12))
+			.map_err(|e| CliError::ArgumentError(Cow::Owned(format!(
+				"disc title has an invalid character at position {}", e.position()
+			))))?;
+		if !args.no_increment_cycle {
+			disc.increment_cycle();
+		}
+
+		(disc.raw_title_bytes(), disc.cycle())
+	};
+
+	image_data[0x000..0x008].copy_from_slice(&title[..8]);
+	image_data[0x100..0x104].copy_from_slice(&title[8..]);
+	image_data[0x104] = cycle.into_hex();
+
+	write_image(&args.image_file, &image_data)
+}
+
+/// Rewrites an image with its files laid out in the given order.
+fn sc_sort(args: &ScSort) -> CliResult {
+	let image_data = read_image_for_update(&args.image_file)?;
+	let mut disc = open_disc(&image_data)?;
+
+	disc.sort_files(args.by);
+
+	disc.set_auto_increment_cycle(!args.no_increment_cycle);
+	let new_image = disc.to_vec()?;
+	drop(disc);
+
+	write_image(&args.image_file, &new_image)
+}
+
+/// Prints the differences between two images: disc attributes first, then
+/// added (`+`), removed (`-`) and modified (`~`) files.
+fn sc_diff(args: &ScDiff) -> CliResult {
+	let old_data = read_image(&args.old_image)?;
+	let new_data = read_image(&args.new_image)?;
+	let old = open_disc(&old_data)?;
+	let new = open_disc(&new_data)?;
+
+	let diff = old.diff(&new);
+
+	if let Some((a, b)) = diff.name {
+		println!("title: '{}' -> '{}'", a, b);
+	}
+	if let Some((a, b)) = diff.boot_option {
+		println!("boot: {} -> {}", a.as_str(), b.as_str());
+	}
+	if let Some((a, b)) = diff.cycle {
+		println!("cycle: {} -> {}", a, b);
+	}
+	for file in &diff.added {
+		println!("+ {}", file);
+	}
+	for file in &diff.removed {
+		println!("- {}", file);
+	}
+	for changes in &diff.modified {
+		let mut details = Vec::new();
+		if let Some((a, b)) = changes.load_addr {
+			details.push(format!("load {:x} -> {:x}", a, b));
+		}
+		if let Some((a, b)) = changes.exec_addr {
+			details.push(format!("exec {:x} -> {:x}", a, b));
+		}
+		if let Some((_, locked)) = changes.locked {
+			details.push(String::from(if locked { "locked" } else { "unlocked" }));
+		}
+		if changes.content {
+			details.push(String::from("content"));
+		}
+		println!("~ {} ({})", changes.file, details.join(", "));
+	}
+
+	Ok(())
+}
+
+/// Reports any damage to an image, with the offset of each problem. Damage
+/// that stops the image being read at all is an error; anything else is a
+/// warning.
+fn sc_verify(args: &ScVerify) -> CliResult {
+	let image_data = read_image(&args.image_file)?;
+	if !dfs::looks_like_dfs(&image_data) {
+		return Err(not_dfs());
+	}
+	let issues = dfs::Disc::validate(&image_data)?;
+	let result = match args.strict {
+		true => dfs::Disc::from_bytes_strict(&image_data),
+		false => dfs::Disc::from_bytes(&image_data),
+	};
+
+	let severity = if result.is_ok() { "warning" } else { "error" };
+	for issue in &issues {
+		println!("{} at 0x{:03x}: {}", severity, issue.offset(), issue);
+	}
+	let boot_warning = result.as_ref().ok().and_then(boot_warning);
+	if let Some(ref warning) = boot_warning {
+		println!("warning: {}", warning);
+	}
+	if issues.is_empty() && boot_warning.is_none() && result.is_ok() {
+		println!("ok");
+	}
+
+	result.map(|_| ()).map_err(CliError::BadImage)
+}
+
+/// Explains why a disc's boot option won't work, if it won't.
+fn boot_warning(disc: &dfs::Disc) -> Option<String> {
+	if disc.boot_target_valid() {
+		return None;
+	}
+	let elsewhere: Vec<String> = disc.files()
+		.filter(|f| f.name().as_str().eq_ignore_ascii_case("!BOOT"))
+		.map(|f| format!("{}.{}", f.dir(), f.name()))
+		.collect();
+	Some(match elsewhere.is_empty() {
+		true => format!("boot option is {}, but there is no $.!BOOT", disc.boot_option().as_str()),
+		false => format!("boot option is {}, but there is no $.!BOOT (only {})",
+			disc.boot_option().as_str(), elsewhere.join(", ")),
+	})
+}
+
+/// Creates a blank disc image, as if freshly formatted. Two-sided images are
+/// written track-interleaved, with the same title on both sides.
+fn sc_create(args: &ScCreate) -> CliResult {
+	let geometry = match args.tracks {
+		40 => dfs::Geometry::Tracks40,
+		80 => dfs::Geometry::Tracks80,
+		_ => return Err(CliError::ArgumentError(Cow::Borrowed("tracks must be 40 or 80"))),
+	};
+	if !(1..=2).contains(&args.sides) {
+		return Err(CliError::ArgumentError(Cow::Borrowed("sides must be 1 or 2")));
+	}
+	refuse_overwrite(&args.image_file, args.force)?;
+
+	let mut disc = dfs::Disc::new_formatted(geometry);
+	if let Some(ref name) = args.name {
+		let name = AsciiPrintingStr::try_from_str(name)
+			.map_err(|_| CliError::ArgumentError(Cow::Borrowed(
+				"disc title has non-printing or non-ASCII characters"
+			)))?;
+		if name.len() > 12 {
+			return Err(CliError::ArgumentError(Cow::Borrowed(
+				"disc title is longer than 12 characters"
+			)));
+		}
+		disc.set_name(name)
+			.map_err(|e| CliError::ArgumentError(Cow::Owned(format!(
+				"disc title has an invalid character at position {}", e.position()
+			))))?;
+	}
+	if let Some(boot) = args.boot {
+		*disc.boot_option_mut() = boot;
+	}
+
+	let side = disc.to_vec()?;
+
+	let image = match args.sides {
+		1 => side,
+		_ => dfs::interleave_sides(&side, &side),
+	};
+	write_image(&args.image_file, &image)
+}
+
+/// Interleaves two images track by track. Both are checked first, but
+/// written as they are, so nothing in them is lost.
+fn sc_join(args: &ScJoin) -> CliResult {
+	refuse_overwrite(&args.output, args.force)?;
+	let side0 = read_image(&args.side0)?;
+	let side1 = read_image(&args.side1)?;
+	open_disc(&side0)?;
+	open_disc(&side1)?;
+	write_image(&args.output, &dfs::interleave_sides(&side0, &side1))
+}
+
+/// Takes a double-sided image apart. Both sides are checked before either
+/// is written.
+fn sc_split(args: &ScSplit) -> CliResult {
+	refuse_overwrite(&args.side0, args.force)?;
+	refuse_overwrite(&args.side1, args.force)?;
+	let image = read_image(&args.image_file)?;
+	let (side0, side1) = dfs::split_sides(&image);
+	open_disc(&side0)?;
+	open_disc(&side1)?;
+	write_image(&args.side0, &side0)?;
+	write_image(&args.side1, &side1)
+}
+
+fn refuse_overwrite(path: &OsStr, force: bool) -> CliResult {
+	if !force && path != "-" && Path::new(path).exists() {
+		return Err(CliError::ArgumentError(Cow::Owned(format!(
+			"'{}' already exists; use --force to overwrite it", Path::new(path).display()
+		))));
+	}
+	Ok(())
+}
+
 trait FileHeuristics {
 	fn is_mos_text(&self) -> bool;
 	fn looks_like_basic(&self) -> bool;
@@ -299,7 +1200,7 @@ impl FileHeuristics for [u8] {
 		const CR: u8 = b'\r';
 		const PRINTING_LOW : u8 = b'\x21';
 		const PRINTING_HIGH: u8 = b'\x7e';
-		self.iter().all(|&b| b == CR || (b >= PRINTING_LOW && b <= PRINTING_HIGH))
+		self.iter().all(|&b| b == CR || (PRINTING_LOW..=PRINTING_HIGH).contains(&b))
 	}
 
 	fn looks_like_basic(&self) -> bool {
@@ -308,7 +1209,7 @@ impl FileHeuristics for [u8] {
 }
 
 
-fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
+fn sc_pack(args: &ScPack) -> CliResult {
 	use xml::reader::XmlEvent;
 
 	macro_rules! dfs_error {
@@ -322,16 +1223,18 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 		};
 	}
 
+	let manifest_path: &Path = args.manifest.as_ref();
+	let image_path: &Path = args.output_file.as_ref();
+
 	let root = std::fs::canonicalize(manifest_path)
 		.map_err(CliError::Io)?;
+	// file paths in the manifest are relative to it
+	let base = root.parent().unwrap();
 
 	// open and parse manifest file
 	let mut reader = File::open(&*root)
 		.map(xml::EventReader::new)?;
 
-	// CD to path folder
-	std::env::set_current_dir(root.parent().unwrap())?;
-
 	// load files
 
 	// - attempt to get root element
@@ -341,13 +1244,12 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 	};
 	let mut disc = match reader.next()? {
 		XmlEvent::StartElement {name: _, attributes, namespace} => {
-			match namespace.get(xml::namespace::NS_NO_PREFIX) {
-				Some(XML_NAMESPACE) => {},
-				Some(_other) => warn!("document has unexpected XML namespace; wanted '{}'", XML_NAMESPACE),
-				None => warn!("document has no XML namespace; expected '{}'", XML_NAMESPACE),
-			};
+			if let Some(warning) = namespace_warning(namespace.get(xml::namespace::NS_NO_PREFIX), args.lenient) {
+				warn!("{}", warning);
+			}
 
 			let mut disc = dfs::Disc::new();
+			disc.set_strict_names(true);
 
 			if let Some(name) = attributes.local_attr("name") {
 				let ap_name = AsciiPrintingStr::try_from_str(name)
@@ -358,16 +1260,23 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 			}
 
 			if let Some(cycle) = attributes.local_attr("cycle") {
-				*disc.cycle_mut() = u8::from_str(cycle).ok()
-					.and_then(|r#u8| BCD::from_hex(r#u8).ok())
-					.ok_or_else(|| dfs_error!("incorrect cycle count; not valid 2-digit BCD"))?;
+				// the manifest holds the decimal value, as written by unpack
+				u8::from_str(cycle).ok()
+					.and_then(|r#u8| disc.set_cycle(r#u8).ok())
+					.ok_or(dfs_error!("incorrect cycle count; must be 0-99"))?;
 			}
 
 			if let Some(boot_option) = attributes.local_attr("boot") {
-				match dfs::BootOption::from_str(boot_option) {
-					Ok(bo) => *disc.boot_option_mut() = bo,
-					Err(_) => return Err(dfs_error!("invalid boot option"))
-				};
+				disc.set_boot_option_str(boot_option)
+					.map_err(|_| dfs_error!("invalid boot option"))?;
+			}
+
+			// older versions of unpack always wrote 40, so only 80 is taken
+			// as binding; otherwise the files decide
+			match attributes.local_attr("tracks") {
+				None | Some("40") => {},
+				Some("80") => disc.set_geometry(dfs::Geometry::Tracks80)?,
+				Some(_) => return Err(dfs_error!("invalid track count; must be 40 or 80")),
 			}
 
 			Ok(disc)
@@ -390,6 +1299,9 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 					None => Ok(AsciiPrintingChar::DOLLAR),
 					Some(Err(_)) => Err(dfs_error!("dir is not a printing ascii char")),
 				}?;
+				if !dir.is_valid_dfs_dir() {
+					return Err(dfs_error!("'{}' is reserved by DFS and can't be a dir", dir));
+				}
 
 				let name = match attributes.local_attr("name")
 				.map(|d| AsciiName::<7>::try_from(d.as_bytes())) {
@@ -397,6 +1309,9 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 					None => Err(dfs_error!("filename must be specified")),
 					Some(Err(_)) => Err(dfs_error!("could not convert file name")),
 				}?;
+				if !name.is_addressable() {
+					return Err(dfs_error!("file name '{}' contains characters reserved by DFS", name));
+				}
 
 				let parse_addr = |addr_name: &str| -> Result<u32, CliError> {
 					match attributes.local_attr(addr_name).map(|s| u32::from_str_radix(s, 16)) {
@@ -408,34 +1323,64 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 				let load_addr = parse_addr("load")?;
 				let exec_addr = parse_addr("exec")?;
 
-				let src_path = attributes.local_attr("src")
-					.ok_or_else(|| dfs_error!("src attribute is missing"))?;
-				let mut src = File::open(src_path)?;
-				if src.metadata().map(|m| m.len()).unwrap_or(u64::MAX) > dfs::MAX_DISC_SIZE {
-					return Err(dfs_error!("file '{}' is too big to fit", src_path))?;
+				// contents can be inline, as base64, instead of in a src file
+				let mut inline = String::new();
+				loop {
+					match reader.next()? {
+						XmlEvent::EndElement { name } if name.local_name == element_name => break,
+						XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => inline.push_str(&text),
+						XmlEvent::Comment(_) => {},
+						o => return Err(dfs_error!("uncrecognised element {:?}, was expecting </{}>",
+							o, element_name)),
+					};
 				}
-				// get file contents
-				let contents = {
-					let mut c = Vec::new();
-					src.read_to_end(&mut c)?;
-					c
+
+				let (src_path, contents) = match (attributes.local_attr("src"), inline.trim().is_empty()) {
+					(Some(_), false) => return Err(dfs_error!(
+						"file '{}.{}' has both a src attribute and inline content", dir, name)),
+					(Some(src_path), true) => {
+						let mut src = File::open(base.join(src_path))?;
+						if src.metadata().map(|m| m.len()).unwrap_or(u64::MAX) > dfs::MAX_DISC_SIZE {
+							return Err(dfs_error!("file '{}' is too big to fit", src_path));
+						}
+						// get file contents
+						let mut c = Vec::new();
+						src.read_to_end(&mut c)?;
+						(src_path, c)
+					},
+					(None, false) => ("inline content", base64::decode(&inline)
+						.ok_or(dfs_error!("inline content of '{}.{}' is not valid base64", dir, name))?),
+					// unpack --inline writes an empty file as an empty element
+					(None, true) => ("inline content", Vec::new()),
 				};
 
-				match disc.add_file(dfs::File::new(name, dir, load_addr, exec_addr,
-				false, /* TODO */
+				// optional, but catches a src file edited without updating the manifest
+				if let Some(length) = attributes.local_attr("length") {
+					let length = usize::from_str_radix(length, 16)
+						.map_err(|_| dfs_error!("couldn't parse length of {}.{}", dir, name))?;
+					if let Some(warning) = length_warning(src_path, contents.len(), length) {
+						warn!("{}", warning);
+					}
+				}
+
+				let locked = match attributes.local_attr("locked") {
+					None | Some("false") | Some("0") => false,
+					Some("true") | Some("1") => true,
+					Some(_) => return Err(dfs_error!(
+						"locked attribute of {}.{} must be true or false", dir, name)),
+				};
+
+				match disc.add_file(dfs::File::new(name, dir, load_addr, exec_addr, locked,
 				Cow::Owned(contents))) {
 					Ok(None) => {},
 					Ok(Some(old)) => warn!("replacing existing file '{}.{}'", old.dir(), old.name()),
-					Err(failed) => return Err(
-						dfs_error!("file '{}.{}' was specified twice", failed.dir(), failed.name())
+					Err((e @ dfs::DFSError::CatalogueFull { .. }, failed)) => return Err(dfs_error!(
+						"{}; can't add '{}.{}'", e, failed.dir(), failed.name()
+					)),
+					Err((_, failed)) => return Err(
+						dfs_error!("file '{}.{}' can't be added", failed.dir(), failed.name())
 					),
 				};
-
-				match reader.next()? {
-					XmlEvent::EndElement { name } if name.local_name == element_name => {},
-					o => return Err(dfs_error!("uncrecognised element {:?}, was expecting </{}>",
-						o, element_name)),
-				};
 			},
 			XmlEvent::EndElement {name} if name.local_name == "dfsdisc" => break,
 			XmlEvent::Whitespace(_) | XmlEvent::Comment(_) => {}, // who care
@@ -443,6 +1388,12 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 		};
 	}
 
+	if let Some(ref add_dir) = args.add_dir {
+		let add_dir = Path::new(add_dir);
+		let added = disc.add_dir(add_dir, AsciiPrintingChar::DOLLAR, args.collisions)?;
+		eprintln!("added {} files from {}", added, add_dir.display());
+	}
+
 	// write it out to target
 	let mut target = File::create(image_path)?;
 	disc.to_image(&mut target)?;
@@ -450,6 +1401,27 @@ fn sc_pack(manifest_path: &Path, image_path: &Path) -> CliResult {
 	Ok(())
 }
 
+/// Checks the manifest's default namespace. A manifest with no namespace is
+/// fine when lenient, as it's the obvious way to write one by hand; one
+/// with a different namespace is always suspect.
+fn namespace_warning(namespace: Option<&str>, lenient: bool) -> Option<String> {
+	match namespace {
+		Some(XML_NAMESPACE) => None,
+		Some(_other) => Some(format!("document has unexpected XML namespace; wanted '{}'", XML_NAMESPACE)),
+		None if lenient => None,
+		None => Some(format!("document has no XML namespace; expected '{}'", XML_NAMESPACE)),
+	}
+}
+
+/// Checks a file's length against the one its manifest entry gives.
+fn length_warning(src_path: &str, actual: usize, declared: usize) -> Option<String> {
+	if actual == declared {
+		None
+	} else {
+		Some(format!("'{}' is 0x{:x} bytes, but the manifest says 0x{:x}", src_path, actual, declared))
+	}
+}
+
 trait AttributesExt {
 	type Attr: ?Sized;
 
@@ -465,3 +1437,450 @@ impl AttributesExt for [xml::attribute::OwnedAttribute] {
 			.map(|attr| attr.value.as_str())
 	}
 }
+
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// A scratch directory, removed along with its contents when dropped.
+	struct TempDir(std::path::PathBuf);
+
+	impl TempDir {
+		fn new(name: &str) -> TempDir {
+			let path = std::env::temp_dir().join(format!("dfsdisc-{}-{}", name, std::process::id()));
+			let _ = std::fs::remove_dir_all(&path);
+			std::fs::create_dir_all(&path).unwrap();
+			TempDir(path)
+		}
+	}
+
+	impl std::ops::Deref for TempDir {
+		type Target = Path;
+
+		fn deref(&self) -> &Path { &self.0 }
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.0);
+		}
+	}
+
+	#[test]
+	fn dfs_path() {
+		let (dir, name) = parse_dfs_path("B.FOO").unwrap();
+		assert_eq!(b'B', dir.as_byte());
+		assert_eq!("FOO", name);
+
+		let (dir, name) = parse_dfs_path("BAR").unwrap();
+		assert_eq!(AsciiPrintingChar::DOLLAR, dir);
+		assert_eq!("BAR", name);
+
+		assert!(parse_dfs_path("$.TOOLONGX").is_err());
+		assert!(parse_dfs_path("*.FOO").is_err());
+	}
+
+	#[test]
+	fn probe_json_output() {
+		assert_eq!(r#""a\"b\\c\u0001""#, json_string("a\"b\\c\u{1}"));
+
+		let mut disc = dfs::Disc::new();
+		disc.set_name(AsciiPrintingStr::try_from_str("Disc \"1\"").unwrap()).unwrap();
+		let name = dfs::FileName::try_from(&b"FOO"[..]).unwrap();
+		disc.add_file(dfs::File::new(name, AsciiPrintingChar::DOLLAR, 0x1900, 0x8023, true,
+			Cow::Borrowed(b"abc"))).unwrap();
+		assert_eq!(r#"{"name":"Disc \"1\"","cycle":"00","boot":"none","tracks":40,"sides":1,"#.to_owned()
+			+ r#""files":[{"dir":"$","name":"FOO","load":"1900","exec":"8023","length":3,"locked":true}]}"#,
+			probe_json(&disc));
+	}
+
+	#[test]
+	fn ls_output() {
+		let mut disc = dfs::Disc::new();
+		for (dir, name, locked) in [(b'B', &b"DATA"[..], false), (b'$', b"!BOOT", true)] {
+			disc.add_file(dfs::File::new(dfs::FileName::try_from(name).unwrap(),
+				AsciiPrintingChar::from(dir).unwrap(), 0x1900, 0x8023, locked, Cow::Borrowed(b"abc")))
+				.unwrap();
+		}
+
+		assert_eq!("$.!BOOT\nB.DATA\n", list_files(&disc, None, false, None));
+		assert_eq!("B.DATA\n", list_files(&disc, AsciiPrintingChar::from(b'B').ok(), false, None));
+		assert_eq!("$.!BOOT   001900 008023 000003 L\nB.DATA    001900 008023 000003\n",
+			list_files(&disc, None, true, None));
+		assert_eq!("$.!BOOT   &1900   &8023   &0003 L\nB.DATA    &1900   &8023   &0003\n",
+			list_files(&disc, None, true, Some(AddrFormat::Acorn)));
+		assert_eq!("$.!BOOT   6400    32803   3 L\nB.DATA    6400    32803   3\n",
+			list_files(&disc, None, true, Some(AddrFormat::Dec)));
+		assert_eq!(Ok(AddrFormat::C), parse_addr_format("C"));
+	}
+
+	#[test]
+	fn verify_boot_file() {
+		let mut disc = dfs::Disc::new();
+		*disc.boot_option_mut() = dfs::BootOption::Run;
+		assert_eq!(Some("boot option is run, but there is no $.!BOOT".into()), boot_warning(&disc));
+
+		let boot = dfs::FileName::try_from(&b"!BOOT"[..]).unwrap();
+		disc.add_file(dfs::File::data(boot.clone(), AsciiPrintingChar::from(b'B').unwrap(),
+			Cow::Borrowed(b"CHAIN\"GAME\"\r"))).unwrap();
+		assert_eq!(Some("boot option is run, but there is no $.!BOOT (only B.!BOOT)".into()),
+			boot_warning(&disc));
+
+		disc.add_file(dfs::File::data(boot, AsciiPrintingChar::DOLLAR, Cow::Borrowed(b""))).unwrap();
+		assert_eq!(None, boot_warning(&disc));
+	}
+
+	#[test]
+	fn unpack_boot_file() {
+		let base = TempDir::new("bootfile");
+
+		let mut disc = dfs::Disc::new();
+		*disc.boot_option_mut() = dfs::BootOption::Exec;
+		disc.add_file(dfs::File::data(dfs::FileName::try_from(&b"!Boot"[..]).unwrap(),
+			AsciiPrintingChar::DOLLAR, Cow::Borrowed(b"*RUN GAME\r"))).unwrap();
+		let manifest = |disc: &dfs::Disc, name: &str| {
+			let image_path = base.join(name).with_extension("ssd");
+			std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+			let out = base.join(name);
+			sc_unpack(image_path.as_os_str(), out.as_os_str(), true, None, dfs::SortKey::Directory)
+				.unwrap();
+			std::fs::read_to_string(out.join("manifest.xml")).unwrap()
+		};
+
+		assert!(manifest(&disc, "exec").contains(r#"boot="exec" boot-file="$.!Boot""#));
+		*disc.boot_option_mut() = dfs::BootOption::None;
+		assert!(!manifest(&disc, "none").contains("boot-file"));
+	}
+
+	#[test]
+	fn dump_catalogue_sector() {
+		let mut disc = dfs::Disc::new();
+		disc.set_name(AsciiPrintingStr::try_from_str("RAW").unwrap()).unwrap();
+		let image = disc.to_vec().unwrap();
+
+		let dump = dump_sectors(&image, 0, 0);
+		assert_eq!(16, dump.lines().count());
+		assert!(dump.starts_with("00000000  52 41 57 20 20 20 20 20"));
+		assert!(dump.lines().last().unwrap().starts_with("000000f0  "));
+
+		assert_eq!(16, dump_sectors(&image, 1, 9).lines().count());
+		assert_eq!("", dump_sectors(&image, 2, 5));
+
+		assert_eq!(Ok(SectorRange { first: 2, last: 5 }), parse_sector_range("2-5"));
+		assert_eq!(Ok(SectorRange { first: 7, last: 7 }), parse_sector_range("7"));
+		assert!(parse_sector_range("5-2").is_err());
+		assert!(parse_sector_range("x").is_err());
+	}
+
+	#[test]
+	fn sector_map_output() {
+		let mut disc = dfs::Disc::new();
+		disc.set_total_sectors(12).unwrap();
+		let name = dfs::FileName::try_from(&b"FOO"[..]).unwrap();
+		disc.add_file(dfs::File::data(name, AsciiPrintingChar::DOLLAR,
+			Cow::Borrowed(&[0; 0x101]))).unwrap();
+		disc.place_files().unwrap();
+		assert_eq!("  0 ##AA......\n  1 ..\nA $.FOO\n", sector_map(&disc));
+	}
+
+	#[test]
+	fn unpack_in_parallel() {
+		let base = TempDir::new("unpack");
+
+		let threads: Vec<_> = (0..2).map(|i| {
+			let base = base.to_path_buf();
+			std::thread::spawn(move || {
+				let mut disc = dfs::Disc::new();
+				let name = dfs::FileName::try_from(format!("FILE{}", i).as_bytes()).unwrap();
+				disc.add_file(dfs::File::data(name, AsciiPrintingChar::DOLLAR,
+					Cow::Owned(vec![b'0' + i]))).unwrap();
+				let image_path = base.join(format!("{}.ssd", i));
+				std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+
+				sc_unpack(image_path.as_os_str(), base.join(format!("out{}", i)).as_os_str(), false, None, dfs::SortKey::Directory).unwrap();
+			})
+		}).collect();
+		for thread in threads {
+			thread.join().unwrap();
+		}
+
+		let unpacked = |path: &str| std::fs::read(base.join(path)).ok();
+		assert_eq!(Some(b"0".to_vec()), unpacked("out0/$/FILE0"));
+		assert_eq!(Some(b"1".to_vec()), unpacked("out1/$/FILE1"));
+		assert_eq!(None, unpacked("out0/$/FILE1"));
+		assert!(base.join("out1/manifest.xml").is_file());
+	}
+
+	#[test]
+	fn unpack_is_reproducible() {
+		let base = TempDir::new("repro");
+
+		let mut files = Vec::new();
+		for (i, dir) in b"$ABZQ".iter().enumerate() {
+			for name in ["ONE", "TWO", "THREE"] {
+				files.push((name, *dir, vec![i as u8; i * 100]));
+			}
+		}
+
+		// the same files, added in a different order each time
+		let manifests: Vec<_> = (0..2).map(|i| {
+			let mut disc = dfs::Disc::new();
+			for (name, dir, content) in &files {
+				let name = dfs::FileName::try_from(name.as_bytes()).unwrap();
+				disc.add_file(dfs::File::data(name, AsciiPrintingChar::from(*dir).unwrap(),
+					Cow::Owned(content.clone()))).unwrap();
+			}
+			files.reverse();
+
+			let image_path = base.join(format!("disc{}.ssd", i));
+			std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+			let out = base.join(format!("out{}", i));
+			sc_unpack(image_path.as_os_str(), out.as_os_str(), false, None, dfs::SortKey::Directory).unwrap();
+			std::fs::read(out.join("manifest.xml")).unwrap()
+		}).collect();
+		assert_eq!(manifests[0], manifests[1]);
+	}
+
+	#[test]
+	fn unpack_sort() {
+		let base = TempDir::new("sort");
+
+		let mut disc = dfs::Disc::new();
+		for (dir, name) in [(b'B', "ZED"), (b'$', "TWO"), (b'B', "ALPHA"), (b'$', "ONE")] {
+			let name = dfs::FileName::try_from(name.as_bytes()).unwrap();
+			disc.add_file(dfs::File::data(name, AsciiPrintingChar::from(dir).unwrap(),
+				Cow::Borrowed(b"x"))).unwrap();
+		}
+		let image_path = base.join("disc.ssd");
+		std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+
+		let order = |sort: dfs::SortKey| -> Vec<String> {
+			let out = base.join(sort.as_str());
+			sc_unpack(image_path.as_os_str(), out.as_os_str(), true, None, sort).unwrap();
+			let manifest = std::fs::read_to_string(out.join("manifest.xml")).unwrap();
+			manifest.split("name=\"").skip(2) // first one is the disc's
+				.map(|rest| rest[..rest.find('"').unwrap()].to_owned())
+				.collect()
+		};
+		assert_eq!(vec!["ONE", "TWO", "ALPHA", "ZED"], order(dfs::SortKey::Directory));
+		assert_eq!(vec!["ALPHA", "ONE", "TWO", "ZED"], order(dfs::SortKey::Name));
+		let on_disc: Vec<String> = dfs::Disc::from_bytes(&std::fs::read(&image_path).unwrap())
+			.unwrap().files().map(|f| f.name().to_string()).collect();
+		assert_eq!(on_disc, order(dfs::SortKey::CatalogueIndex));
+	}
+
+	#[test]
+	fn unpack_newlines() {
+		let base = TempDir::new("newline");
+
+		let mut disc = dfs::Disc::new();
+		for (name, content) in [("TEXT", &b"LINE1\rLINE2\r"[..]), ("DATA", b"\x00\r\x01")] {
+			let name = dfs::FileName::try_from(name.as_bytes()).unwrap();
+			disc.add_file(dfs::File::new(name, AsciiPrintingChar::DOLLAR, 0, 0xffff, false,
+				Cow::Borrowed(content))).unwrap();
+		}
+		let image_path = base.join("text.ssd");
+		std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+		sc_unpack(image_path.as_os_str(), base.as_os_str(), false,
+			Some(dfs::NewlineStyle::Crlf), dfs::SortKey::Directory).unwrap();
+
+		let unpacked = |path: &str| std::fs::read(base.join(path)).unwrap();
+		assert_eq!(b"LINE1\r\nLINE2\r\n".to_vec(), unpacked("$/TEXT"));
+		assert_eq!(b"\x00\r\x01".to_vec(), unpacked("$/DATA"));
+	}
+
+	#[test]
+	fn pack_without_namespace() {
+		assert_eq!(None, namespace_warning(None, true));
+		assert!(namespace_warning(None, false).is_some());
+		assert_eq!(None, namespace_warning(Some(XML_NAMESPACE), false));
+		assert!(namespace_warning(Some("urn:other"), true).is_some());
+
+		let base = TempDir::new("pack-ns");
+		std::fs::write(base.join("hello.bin"), b"hello").unwrap();
+		std::fs::write(base.join("manifest.xml"), concat!(
+			r#"<?xml version="1.0"?><dfsdisc name="PLAIN">"#,
+			r#"<data name="HELLO" load="1900" exec="1900" src="hello.bin"/></dfsdisc>"#,
+		)).unwrap();
+
+		let args = ScPack::parse_args_default(&[
+			"-x", base.join("manifest.xml").to_str().unwrap(),
+			"--lenient", base.join("out.ssd").to_str().unwrap(),
+		]).unwrap();
+		sc_pack(&args).unwrap();
+		let image = std::fs::read(base.join("out.ssd")).unwrap();
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!("PLAIN", disc.name());
+		assert_eq!(1, disc.files().count());
+	}
+
+	#[test]
+	fn pack_length_mismatch() {
+		assert_eq!(None, length_warning("hello.bin", 5, 5));
+		assert_eq!(Some("'hello.bin' is 0x5 bytes, but the manifest says 0x10".to_owned()),
+			length_warning("hello.bin", 5, 0x10));
+
+		let base = TempDir::new("pack-length");
+		std::fs::write(base.join("hello.bin"), b"hello").unwrap();
+		let manifest = |length: &str| std::fs::write(base.join("manifest.xml"), format!(concat!(
+			r#"<?xml version="1.0"?><dfsdisc xmlns="{}" name="LENGTH">"#,
+			r#"<data name="HELLO" load="1900" exec="1900" length="{}" src="hello.bin"/></dfsdisc>"#,
+		), XML_NAMESPACE, length)).unwrap();
+		let args = ScPack::parse_args_default(&[
+			"-x", base.join("manifest.xml").to_str().unwrap(), base.join("out.ssd").to_str().unwrap(),
+		]).unwrap();
+
+		// a wrong length is only a warning; the src file wins
+		manifest("10");
+		sc_pack(&args).unwrap();
+		let image = std::fs::read(base.join("out.ssd")).unwrap();
+		let disc = dfs::Disc::from_bytes(&image).unwrap();
+		assert_eq!(b"hello", disc.files().next().unwrap().content());
+
+		manifest("five");
+		assert!(sc_pack(&args).is_err());
+	}
+
+	#[test]
+	fn pack_inline() {
+		let base = TempDir::new("pack-inline");
+
+		let mut disc = dfs::Disc::new();
+		for (name, content) in [("EMPTY", &b""[..]), ("HELLO", b"hello")] {
+			disc.add_file(dfs::File::data(dfs::FileName::try_from(name.as_bytes()).unwrap(),
+				AsciiPrintingChar::DOLLAR, Cow::Borrowed(content))).unwrap();
+		}
+		let image_path = base.join("in.ssd");
+		std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+		sc_unpack(image_path.as_os_str(), base.join("out").as_os_str(), true, None,
+			dfs::SortKey::CatalogueIndex).unwrap();
+
+		let pack = |manifest: &Path| {
+			let args = ScPack::parse_args_default(&[
+				"-x", manifest.to_str().unwrap(),
+				"--lenient", base.join("packed.ssd").to_str().unwrap(),
+			]).unwrap();
+			sc_pack(&args).map(|()| std::fs::read(base.join("packed.ssd")).unwrap())
+		};
+		let image = pack(&base.join("out/manifest.xml")).unwrap();
+		assert_eq!(disc, dfs::Disc::from_bytes(&image).unwrap());
+
+		std::fs::write(base.join("hello.bin"), b"hello").unwrap();
+		std::fs::write(base.join("both.xml"), concat!(r#"<?xml version="1.0"?><dfsdisc>"#,
+			r#"<data name="HELLO" load="0" exec="0" src="hello.bin">aGVsbG8=</data></dfsdisc>"#,
+		)).unwrap();
+		match pack(&base.join("both.xml")) {
+			Err(CliError::ManifestError(e)) =>
+				assert_eq!("file '$.HELLO' has both a src attribute and inline content", e),
+			other => panic!("unexpected result: {:?}", other.map(|_| ())),
+		}
+	}
+
+	#[test]
+	fn pack_keeps_locked() {
+		let base = TempDir::new("pack-locked");
+
+		let mut disc = dfs::Disc::new();
+		for (name, locked) in [("OPEN", false), ("SHUT", true)] {
+			disc.add_file(dfs::File::new(dfs::FileName::try_from(name.as_bytes()).unwrap(),
+				AsciiPrintingChar::DOLLAR, 0, 0, locked, Cow::Borrowed(b"x"))).unwrap();
+		}
+		let image_path = base.join("in.ssd");
+		std::fs::write(&image_path, disc.to_vec().unwrap()).unwrap();
+		sc_unpack(image_path.as_os_str(), base.join("out").as_os_str(), true, None,
+			dfs::SortKey::Directory).unwrap();
+		let manifest = std::fs::read_to_string(base.join("out/manifest.xml")).unwrap();
+		assert_eq!(1, manifest.matches(r#"locked="true""#).count());
+
+		let pack = |manifest: &str| {
+			std::fs::write(base.join("manifest.xml"), manifest).unwrap();
+			let args = ScPack::parse_args_default(&[
+				"-x", base.join("manifest.xml").to_str().unwrap(),
+				"--lenient", base.join("packed.ssd").to_str().unwrap(),
+			]).unwrap();
+			sc_pack(&args).map(|()| std::fs::read(base.join("packed.ssd")).unwrap())
+		};
+		let image = pack(&manifest).unwrap();
+		let packed = dfs::Disc::from_bytes(&image).unwrap();
+		let locked: Vec<bool> = packed.files().map(|f| f.is_locked()).collect();
+		assert_eq!(vec![false, true], locked);
+
+		for (value, expected) in [("1", Some(true)), ("0", Some(false)), ("yes", None)] {
+			let manifest = format!(concat!(r#"<?xml version="1.0"?><dfsdisc>"#,
+				r#"<data name="F" load="0" exec="0" locked="{}">eA==</data></dfsdisc>"#), value);
+			let locked = pack(&manifest).ok()
+				.map(|image| dfs::Disc::from_bytes(&image).unwrap().files().next().unwrap().is_locked());
+			assert_eq!(expected, locked);
+		}
+	}
+
+	#[test]
+	fn pack_too_many_files() {
+		let base = TempDir::new("pack-full");
+		let mut manifest = String::from(r#"<?xml version="1.0"?><dfsdisc>"#);
+		for i in 0..=dfs::MAX_FILES {
+			manifest += &format!(r#"<data name="F{}" load="0" exec="0">eA==</data>"#, i);
+		}
+		manifest += "</dfsdisc>";
+		std::fs::write(base.join("manifest.xml"), manifest).unwrap();
+
+		let args = ScPack::parse_args_default(&[
+			"-x", base.join("manifest.xml").to_str().unwrap(),
+			"--lenient", base.join("out.ssd").to_str().unwrap(),
+		]).unwrap();
+		match sc_pack(&args) {
+			Err(CliError::ManifestError(e)) =>
+				assert_eq!("disc catalogue is full (max 31 files); can't add '$.F31'", e),
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[cfg(feature = "zip")]
+	#[test]
+	fn add_refuses_zip() {
+		let base = TempDir::new("add-zip");
+
+		let image = dfs::Disc::new().to_vec().unwrap();
+		let archive = zip::stored_zip(&[("GAMES.SSD", &image)]);
+		std::fs::write(base.join("games.zip"), &archive).unwrap();
+		std::fs::write(base.join("x.bin"), b"x").unwrap();
+
+		let args = ScAdd::parse_args_default(&[
+			base.join("games.zip").to_str().unwrap(), "$.X", base.join("x.bin").to_str().unwrap(),
+		]).unwrap();
+		let result = sc_add(&args);
+		let after = std::fs::read(base.join("games.zip")).unwrap();
+
+		match result {
+			Err(CliError::ArgumentError(e)) => assert!(e.contains("is a ZIP archive"), "{}", e),
+			other => panic!("unexpected result: {:?}", other),
+		}
+		assert_eq!(archive, after);
+	}
+
+	#[test]
+	fn pack_without_tracks() {
+		let base = TempDir::new("pack-tracks");
+		std::fs::write(base.join("big.bin"), vec![0x55; 450 * 256]).unwrap();
+
+		let pack = |tracks: &str| {
+			std::fs::write(base.join("manifest.xml"), format!(concat!(
+				r#"<?xml version="1.0"?><dfsdisc name="BIG"{}>"#,
+				r#"<data name="BIG" load="0" exec="0" src="big.bin"/></dfsdisc>"#,
+			), tracks)).unwrap();
+			let args = ScPack::parse_args_default(&[
+				"-x", base.join("manifest.xml").to_str().unwrap(),
+				"--lenient", base.join("out.ssd").to_str().unwrap(),
+			]).unwrap();
+			sc_pack(&args).map(|()| std::fs::read(base.join("out.ssd")).unwrap())
+		};
+		// over 400 sectors: too big for 40 tracks, as older manifests claim
+		for tracks in ["", r#" tracks="40""#] {
+			let image = pack(tracks).unwrap();
+			let disc = dfs::Disc::from_bytes(&image).unwrap();
+			assert_eq!(452, disc.total_sectors());
+			assert_eq!(80, disc.track_count());
+		}
+	}
+}
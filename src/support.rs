@@ -7,7 +7,7 @@ use std::ops::Deref;
 
 use ascii;
 use ascii::{AsciiChar, AsciiStr};
-use arrayvec::ArrayVec;
+use arrayvec::{ArrayVec, CapacityError};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct SliceMinSizeError;
@@ -28,17 +28,25 @@ impl<T, const N: usize> ArrayFromMinSlice<T, N> for [T] {
 				// SAFETY: src.len() ensured to be big enough
 				Ok(&*(self.as_ptr() as *const [T; N]))
 			},
-			_ => return Err(SliceMinSizeError),
+			_ => Err(SliceMinSizeError),
 		}
 	}
 }
 
+/// Sub-slicing that clamps to the end of the slice instead of panicking.
 pub trait SliceExt<T>: AsRef<[T]> {
+	/// The first `limit` elements, or the whole slice if it's shorter.
 	fn up_to(&self, limit: usize) -> &[T] {
 		let slice: &[T] = self.as_ref();
 		&slice[..slice.len().min(limit)]
 	}
 
+	/// The elements in `range`, with both ends clamped to the length of the
+	/// slice. A range wholly past the end gives an empty slice.
+	///
+	/// # Panics
+	/// Panics if `range.start > range.end`.
+	#[allow(clippy::wrong_self_convention)]
 	fn from_up_to(&self, range: std::ops::Range<usize>) -> &[T] {
 		let slice: &[T] = self.as_ref();
 		&slice[std::ops::Range {
@@ -76,32 +84,111 @@ pub fn u16_from_le(src: &[u8]) -> u16 {
 }
 
 
+/// Rounding byte counts up to whole 256-byte sectors.
 pub trait SectorMathExt {
+	/// The number of sectors needed to hold this many bytes. Any partial
+	/// sector counts as a whole one, so 0 bytes take 0 sectors, 1 to 256
+	/// bytes take 1, and 257 take 2.
 	fn sectors(self) -> Self;
 }
 
 impl SectorMathExt for usize {
-	fn sectors(self) -> Self { (self + 0xff) >> 8 }
+	fn sectors(self) -> Self { self / 0x100 + (self % 0x100 != 0) as usize }
 }
 
-pub(crate) trait U8SliceExt {
+/// Copying into fixed-width, space-padded fields, as DFS uses for file
+/// names and the disc title.
+pub trait SpacePadExt {
+	/// Copies `src` to the start of `self`, and fills the rest of `self`
+	/// with spaces (`0x20`). If `src` is longer than `self`, only as much as
+	/// fits is copied.
 	fn copy_space_padded(&mut self, src: &[u8]);
 }
 
-impl U8SliceExt for [u8] {
+impl SpacePadExt for [u8] {
 	fn copy_space_padded(&mut self, src: &[u8]) {
-		assert!(self.len() >= src.len());
-		match (self.len(), src.len()) {
-			(t, s) if t == s => self.copy_from_slice(src),
-			(_, s) => {
-				self[..s].copy_from_slice(src);
-				self[s..].fill(0x20);
+		let (head, tail) = self.split_at_mut(self.len().min(src.len()));
+		head.copy_from_slice(&src[..head.len()]);
+		tail.fill(b' ');
+	}
+}
+
+/// Computes the CRC-16 that Acorn's tape filing system stores with each
+/// block: CCITT polynomial `&1021`, starting from 0, high bit first.
+///
+/// # Examples
+///
+/// ```rust
+/// use dfsdisc::support::crc16_ccitt;
+///
+/// assert_eq!(0x31c3, crc16_ccitt(b"123456789"));
+/// ```
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+	data.iter().fold(0u16, |crc, &byte| {
+		(0..8).fold(crc ^ (byte as u16) << 8, |crc, _| match crc & 0x8000 {
+			0 => crc << 1,
+			_ => crc << 1 ^ 0x1021,
+		})
+	})
+}
+
+/// Formats an address as Acorn tools write it: `&` and at least four
+/// uppercase hex digits. An 18-bit address takes five.
+///
+/// # Examples
+///
+/// ```rust
+/// use dfsdisc::support::fmt_acorn_addr;
+///
+/// assert_eq!("&1900", fmt_acorn_addr(0x1900));
+/// assert_eq!("&30000", fmt_acorn_addr(0x30000));
+/// ```
+pub fn fmt_acorn_addr(addr: u32) -> String {
+	format!("&{:04X}", addr)
+}
+
+/// Formats `bytes` as a canonical hexdump: an address column starting from
+/// `base_addr`, sixteen bytes in hex, and the printable ones in a gutter.
+///
+/// # Examples
+///
+/// ```rust
+/// use dfsdisc::support::hexdump;
+///
+/// assert_eq!("00001900  48 69 0d                                          |Hi.|\n",
+///     hexdump(b"Hi\r", 0x1900).to_string());
+/// ```
+pub fn hexdump(bytes: &[u8], base_addr: u32) -> impl fmt::Display + '_ {
+	HexDump { bytes, base_addr }
+}
+
+struct HexDump<'a> {
+	bytes: &'a [u8],
+	base_addr: u32,
+}
+
+impl<'a> fmt::Display for HexDump<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (i, line) in self.bytes.chunks(16).enumerate() {
+			write!(f, "{:08x} ", self.base_addr.wrapping_add(i as u32 * 16))?;
+			for col in 0..16 {
+				if col == 8 { f.write_str(" ")?; }
+				match line.get(col) {
+					Some(b) => write!(f, " {:02x}", b)?,
+					None => f.write_str("   ")?,
+				};
 			}
+			f.write_str("  |")?;
+			for &b in line {
+				let c = if (0x20..0x7f).contains(&b) { b as char } else { '.' };
+				write!(f, "{}", c)?;
+			}
+			f.write_str("|\n")?;
 		}
+		Ok(())
 	}
 }
 
-
 #[derive(Clone, Copy, Eq, Debug)]
 /// Container for a binary-coded decimal byte.
 pub struct BCD {
@@ -154,7 +241,12 @@ impl BCD {
 
 	/// Converts a `BCD` back into its decimal value.
 	pub fn into_u8(self) -> u8 {
-		(self.value >> 4) + (self.value & 15)
+		(self.value >> 4) * 10 + (self.value & 15)
+	}
+
+	/// Returns the encoded BCD byte, as it would be stored on disc.
+	pub fn into_hex(self) -> u8 {
+		self.value
 	}
 
 	/// Constructs a `BCD` from a pre-encoded BCD representation.
@@ -173,6 +265,13 @@ impl BCD {
 	}
 }
 
+/// Displays the two decimal digits, e.g. `07`.
+impl fmt::Display for BCD {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:02x}", self.value)
+	}
+}
+
 impl PartialEq for BCD {
 	fn eq(&self, other: &Self) -> bool {
 		self.value == other.value
@@ -204,6 +303,12 @@ impl AsciiPrintingChar {
 	}
 
 	pub const DOLLAR: AsciiPrintingChar = Self(AsciiChar::Dollar);
+	pub const SLASH: AsciiPrintingChar = Self(AsciiChar::Slash);
+	pub const SPACE: AsciiPrintingChar = Self(AsciiChar::Space);
+	pub const HASH: AsciiPrintingChar = Self(AsciiChar::Hash);
+	pub const STAR: AsciiPrintingChar = Self(AsciiChar::Asterisk);
+	pub const DOT: AsciiPrintingChar = Self(AsciiChar::Dot);
+	pub const COLON: AsciiPrintingChar = Self(AsciiChar::Colon);
 
 	pub fn try_from_str(s: &str) -> Result<AsciiPrintingChar, AsciiPrintingCharError> {
 		use std::convert::TryFrom;
@@ -215,7 +320,16 @@ impl AsciiPrintingChar {
 		self.0.as_byte()
 	}
 
-	pub fn as_ascii_char(self) -> AsciiChar { self.0 }
+	/// Checks if this character can be used as a DFS directory name.
+	///
+	/// DFS reserves `.` and `:` as path separators, `#` and `*` as
+	/// wildcards, and space as an argument separator; a file in any of these
+	/// directories could not be addressed from the filing system.
+	pub fn is_valid_dfs_dir(&self) -> bool {
+		!matches!(*self, Self::DOT | Self::COLON | Self::HASH | Self::STAR | Self::SPACE)
+	}
+
+	pub const fn as_ascii_char(self) -> AsciiChar { self.0 }
 
 	pub fn as_ascii_str(&self) -> &AsciiStr {
 		std::slice::from_ref(self).as_ascii_str()
@@ -298,15 +412,134 @@ impl<const N: usize> AsciiName<N> {
 		Self { store: ArrayVec::new() }
 	}
 
+	/// Makes a name from any string, for importing names from a modern
+	/// filesystem.
+	///
+	/// Accented Latin-1 letters become their unaccented ASCII forms (`é`
+	/// becomes `e`); any other character that can't be in a name is dropped,
+	/// as is anything past `N` characters. The returned flag is true if any
+	/// character was changed or dropped.
+	pub fn from_transliterated(src: &str) -> (AsciiName<N>, bool) {
+		let mut name = Self::empty();
+		let mut changed = false;
+
+		for c in src.chars() {
+			let mut buf = [0u8; 4];
+			let replacement = match c {
+				c if c.is_ascii() => &*c.encode_utf8(&mut buf),
+				c => { changed = true; latin1_base(c) },
+			};
+			for b in replacement.bytes() {
+				match AsciiPrintingChar::from(b) {
+					Ok(apc) if !name.is_full() => name.store.push(apc),
+					_ => changed = true,
+				}
+			}
+		}
+
+		(name, changed)
+	}
+
 	pub fn as_ascii_str(&self) -> &AsciiStr {
 		(*self.store).as_ascii_str()
 	}
+
+	pub fn as_str(&self) -> &str {
+		self.as_ascii_str().as_str()
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		self.as_ascii_str().as_bytes()
+	}
+
+	/// Iterates over the characters of the name.
+	pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+		self.store.iter().map(|c| c.as_byte() as char)
+	}
+
+	/// Appends a character to the name.
+	///
+	/// # Errors
+	/// Returns a `CapacityError` holding `c` if the name already has `N`
+	/// characters.
+	pub fn push(&mut self, c: AsciiPrintingChar) -> Result<(), CapacityError<AsciiPrintingChar>> {
+		self.store.try_push(c)
+	}
+
+	/// Removes the last character of the name, if there is one.
+	pub fn pop(&mut self) -> Option<AsciiPrintingChar> {
+		self.store.pop()
+	}
+
+	/// A copy of the name with `a` to `z` made uppercase. Everything else
+	/// is left alone.
+	pub fn to_ascii_uppercase(&self) -> AsciiName<N> {
+		let mut name = self.clone();
+		name.make_ascii_uppercase();
+		name
+	}
+
+	/// Makes `a` to `z` in the name uppercase, leaving everything else alone.
+	pub fn make_ascii_uppercase(&mut self) {
+		for c in self.store.iter_mut() {
+			c.0 = c.0.to_ascii_uppercase();
+		}
+	}
+
+	/// Compares the name against `other`, ignoring ASCII case.
+	pub fn eq_ignore_ascii_case(&self, other: &str) -> bool {
+		self.as_str().eq_ignore_ascii_case(other)
+	}
+
+	pub fn len(&self) -> usize { self.store.len() }
+	pub fn is_empty(&self) -> bool { self.store.is_empty() }
+	pub fn is_full(&self) -> bool { self.store.is_full() }
+}
+
+/// The unaccented ASCII form of a Latin-1 letter, or nothing if there isn't
+/// a sensible one.
+fn latin1_base(c: char) -> &'static str {
+	match c {
+		'À'..='Å' => "A", 'à'..='å' => "a",
+		'Æ' => "AE", 'æ' => "ae",
+		'Ç' => "C", 'ç' => "c",
+		'È'..='Ë' => "E", 'è'..='ë' => "e",
+		'Ì'..='Ï' => "I", 'ì'..='ï' => "i",
+		'Ð' => "D", 'ð' => "d",
+		'Ñ' => "N", 'ñ' => "n",
+		'Ò'..='Ö' | 'Ø' => "O", 'ò'..='ö' | 'ø' => "o",
+		'Ù'..='Ü' => "U", 'ù'..='ü' => "u",
+		'Ý' => "Y", 'ý' | 'ÿ' => "y",
+		'Þ' => "TH", 'þ' => "th",
+		'ß' => "ss",
+		_ => "",
+	}
 }
 
 impl<const N: usize> Deref for AsciiName<N> {
 	type Target = [AsciiPrintingChar];
 
-	fn deref(&self) -> &Self::Target { &*self.store }
+	fn deref(&self) -> &Self::Target { &self.store }
+}
+
+impl<const N: usize> PartialEq<str> for AsciiName<N> {
+	fn eq(&self, other: &str) -> bool { self.as_str() == other }
+}
+
+impl<const N: usize> PartialEq<&str> for AsciiName<N> {
+	fn eq(&self, other: &&str) -> bool { self.as_str() == *other }
+}
+
+impl<const N: usize> PartialEq<AsciiName<N>> for str {
+	fn eq(&self, other: &AsciiName<N>) -> bool { self == other.as_str() }
+}
+
+impl<const N: usize> PartialEq<AsciiName<N>> for &str {
+	fn eq(&self, other: &AsciiName<N>) -> bool { *self == other.as_str() }
+}
+
+impl<const N: usize> AsRef<str> for AsciiName<N> {
+	fn as_ref(&self) -> &str { self.as_str() }
 }
 
 impl<const N: usize> std::fmt::Display for AsciiName<N> {
@@ -385,6 +618,20 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn bcd_into_u8() {
+		for i in 0..=99u8 {
+			assert_eq!(i, BCD::try_new(i).unwrap().into_u8());
+		}
+		assert_eq!(0x47, BCD::try_new(47).unwrap().into_hex());
+	}
+
+	#[test]
+	fn bcd_display() {
+		assert_eq!("07", BCD::try_new(7).unwrap().to_string());
+		assert_eq!("42", BCD::try_new(42).unwrap().to_string());
+	}
+
 	#[test]
 	fn bcd_from_hex_success() {
 		let op = |input, output| assert_eq!(Ok(output), BCD::from_hex(input).map(|bcd| bcd.value));
@@ -447,4 +694,153 @@ mod tests {
 			assert!(ch.is_err());
 		}
 	}
+
+	#[test]
+	fn ascii_name_push_pop() {
+		let c = AsciiPrintingChar::from(b'A').unwrap();
+		let mut name = AsciiName::<3>::empty();
+		assert!(name.is_empty());
+
+		for i in 1..=3 {
+			assert_eq!(Ok(()), name.push(c));
+			assert_eq!(i, name.len());
+		}
+		assert!(name.is_full());
+		assert_eq!(c, name.push(c).unwrap_err().element());
+		assert_eq!("AAA", name.as_str());
+
+		assert_eq!(Some(c), name.pop());
+		assert!(!name.is_full());
+		assert_eq!("AA", AsRef::<str>::as_ref(&name));
+	}
+
+	#[test]
+	fn ascii_name_str_eq() {
+		let name = AsciiName::<7>::try_from(&b"Foo"[..]).unwrap();
+		assert_eq!(name, "Foo");
+		assert_eq!("Foo", name);
+		assert!(name == *"Foo");
+		assert_ne!(name, "FOO");
+		assert_ne!(name, "Foo1");
+		assert!(name.eq_ignore_ascii_case("FOO"));
+		assert!(!name.eq_ignore_ascii_case("FOOD"));
+	}
+
+	#[test]
+	fn copy_space_padded() {
+		let mut buf = [0u8; 4];
+		buf.copy_space_padded(b"AB");
+		assert_eq!(b"AB  ", &buf);
+		buf.copy_space_padded(b"WXYZ");
+		assert_eq!(b"WXYZ", &buf);
+		buf.copy_space_padded(b"LONGER");
+		assert_eq!(b"LONG", &buf);
+		buf.copy_space_padded(b"");
+		assert_eq!(b"    ", &buf);
+	}
+
+	#[test]
+	fn up_to() {
+		let src = b"ABCDEFGHIJ";
+		assert_eq!(b"ABCD", src.up_to(4));
+		assert_eq!(src, src.up_to(12));
+		assert_eq!(b"IJ", src.from_up_to(8..12));
+		assert_eq!(b"", src.from_up_to(11..12));
+		assert_eq!(b"CD", src.from_up_to(2..4));
+	}
+
+	#[test]
+	fn sectors() {
+		assert_eq!(0, 0usize.sectors());
+		assert_eq!(1, 1usize.sectors());
+		assert_eq!(1, 256usize.sectors());
+		assert_eq!(2, 257usize.sectors());
+		assert_eq!(800, 204800usize.sectors());
+		// no overflow at the top of the range
+		assert_eq!(usize::MAX / 256 + 1, usize::MAX.sectors());
+	}
+
+	#[test]
+	fn acorn_addr() {
+		assert_eq!("&0000", fmt_acorn_addr(0));
+		assert_eq!("&FFFF", fmt_acorn_addr(0xffff));
+		assert_eq!("&30000", fmt_acorn_addr(0x30000));
+		assert_eq!("&3FFFF", fmt_acorn_addr(0x3ffff));
+	}
+
+	#[test]
+	fn crc16() {
+		assert_eq!(0, crc16_ccitt(b""));
+		assert_eq!(0x31c3, crc16_ccitt(b"123456789"));
+		assert_eq!(0x1021, crc16_ccitt(&[0x01]));
+	}
+
+	#[test]
+	fn hexdump_lines() {
+		let bytes: Vec<u8> = (0x3c..0x51).collect();
+		assert_eq!(
+			"00000ff0  3c 3d 3e 3f 40 41 42 43  44 45 46 47 48 49 4a 4b  |<=>?@ABCDEFGHIJK|\n\
+			 00001000  4c 4d 4e 4f 50                                    |LMNOP|\n",
+			hexdump(&bytes, 0xff0).to_string());
+		assert_eq!("", hexdump(&[], 0).to_string());
+	}
+
+	#[test]
+	fn ascii_name_chars_and_bytes() {
+		let name = AsciiName::<7>::try_from(&b"!BOOT"[..]).unwrap();
+		assert_eq!(b"!BOOT", name.as_bytes());
+		assert_eq!("!BOOT", name.chars().collect::<String>());
+		assert_eq!(0, AsciiName::<7>::empty().chars().count());
+	}
+
+	#[test]
+	fn printing_char_constants() {
+		let all = [AsciiPrintingChar::DOLLAR, AsciiPrintingChar::SLASH, AsciiPrintingChar::SPACE,
+			AsciiPrintingChar::HASH, AsciiPrintingChar::STAR, AsciiPrintingChar::DOT,
+			AsciiPrintingChar::COLON];
+		assert_eq!(b"$/ #*.:", &all.map(|c| c.as_byte()));
+		for c in all {
+			assert_eq!(c, AsciiPrintingChar::from(c.as_byte()).unwrap());
+		}
+	}
+
+	#[test]
+	fn ascii_name_uppercase() {
+		let mut name = AsciiName::<7>::try_from(&b"foo1_{z}"[..7]).unwrap();
+		assert_eq!("FOO1_{Z", name.to_ascii_uppercase());
+		assert_eq!("foo1_{z", name);
+		name.make_ascii_uppercase();
+		assert_eq!("FOO1_{Z", name);
+	}
+
+	#[test]
+	fn ascii_name_transliterated() {
+		let (name, changed) = AsciiName::<7>::from_transliterated("café");
+		assert_eq!("cafe", name);
+		assert!(changed);
+
+		let (name, changed) = AsciiName::<7>::from_transliterated("Plain");
+		assert_eq!("Plain", name);
+		assert!(!changed);
+
+		let (name, changed) = AsciiName::<7>::from_transliterated("Straße\t€1");
+		assert_eq!("Strasse", name);
+		assert!(changed);
+
+		let (name, changed) = AsciiName::<7>::from_transliterated("日本");
+		assert!(name.is_empty());
+		assert!(changed);
+	}
+
+	#[test]
+	fn valid_dfs_dir() {
+		let check = |ch: u8| AsciiPrintingChar::from(ch).unwrap().is_valid_dfs_dir();
+
+		assert!(check(b'$'));
+		assert!(check(b'A'));
+		assert!(check(b'!'));
+		for &reserved in b".:#* " {
+			assert!(!check(reserved), "'{}' accepted as a dir", reserved as char);
+		}
+	}
 }
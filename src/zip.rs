@@ -0,0 +1,180 @@
+//! Just enough ZIP reading to pull a disc image out of an archive.
+//!
+//! Only the central directory is trusted for sizes, as entries written with
+//! a data descriptor have zeroes in their local headers. Stored and
+//! deflated members are supported; encryption and ZIP64 are not.
+
+use std::borrow::Cow;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Checks whether `data` starts like a ZIP archive.
+pub fn is_zip(data: &[u8]) -> bool {
+	read_u32(data, 0) == Some(LOCAL_HEADER_SIG)
+}
+
+/// Extracts the first `.ssd` or `.dsd` member of a ZIP archive, refusing
+/// to produce more than `max_len` bytes.
+pub fn extract_disc_image(archive: &[u8], max_len: usize) -> Result<Vec<u8>, Cow<'static, str>> {
+	let eocd = find_end_of_central_dir(archive)
+		.ok_or(Cow::Borrowed("ZIP archive has no central directory"))?;
+	let entry_count = read_u16(archive, eocd + 10).unwrap_or(0);
+	let mut pos = read_u32(archive, eocd + 16).unwrap_or(u32::MAX) as usize;
+
+	for _ in 0..entry_count {
+		if read_u32(archive, pos) != Some(CENTRAL_HEADER_SIG) {
+			return Err(Cow::Borrowed("ZIP central directory is damaged"));
+		}
+		let field = |offset| read_u16(archive, pos + offset).unwrap_or(0) as usize;
+		let (name_len, extra_len, comment_len) = (field(28), field(30), field(32));
+		let name = archive.get(pos + 46 .. pos + 46 + name_len)
+			.ok_or(Cow::Borrowed("ZIP central directory is damaged"))?;
+
+		if is_disc_image_name(name) {
+			let method = read_u16(archive, pos + 10).unwrap_or(u16::MAX);
+			let compressed_len = read_u32(archive, pos + 20).unwrap_or(0) as usize;
+			let uncompressed_len = read_u32(archive, pos + 24).unwrap_or(0) as usize;
+			let local_header = read_u32(archive, pos + 42).unwrap_or(u32::MAX) as usize;
+			let name = String::from_utf8_lossy(name);
+
+			if uncompressed_len > max_len {
+				return Err(Cow::Owned(format!("'{}' in ZIP archive is too big", name)));
+			}
+			let data = member_data(archive, local_header, compressed_len)
+				.ok_or_else(|| Cow::Owned(format!("'{}' in ZIP archive is damaged", name)))?;
+
+			return match method {
+				METHOD_STORED => Ok(data.to_vec()),
+				METHOD_DEFLATED => miniz_oxide::inflate::decompress_to_vec_with_limit(data, max_len)
+					.map_err(|_| Cow::Owned(format!("'{}' in ZIP archive is damaged", name))),
+				other => Err(Cow::Owned(format!(
+					"'{}' in ZIP archive uses unsupported compression method {}", name, other
+				))),
+			};
+		}
+
+		pos += 46 + name_len + extra_len + comment_len;
+	}
+
+	Err(Cow::Borrowed("ZIP archive has no .ssd or .dsd file"))
+}
+
+fn is_disc_image_name(name: &[u8]) -> bool {
+	let ext = match name.len().checked_sub(4) {
+		Some(start) => &name[start..],
+		None => return false,
+	};
+	ext.eq_ignore_ascii_case(b".ssd") || ext.eq_ignore_ascii_case(b".dsd")
+}
+
+/// The end of central directory record is last in the file, followed by a
+/// comment of up to 64KiB.
+fn find_end_of_central_dir(archive: &[u8]) -> Option<usize> {
+	let last = archive.len().checked_sub(22)?;
+	let first = last.saturating_sub(0xffff);
+	(first..=last).rev().find(|&pos| read_u32(archive, pos) == Some(END_OF_CENTRAL_DIR_SIG))
+}
+
+fn member_data(archive: &[u8], local_header: usize, compressed_len: usize) -> Option<&[u8]> {
+	if read_u32(archive, local_header)? != LOCAL_HEADER_SIG {
+		return None;
+	}
+	let name_len = read_u16(archive, local_header + 26)? as usize;
+	let extra_len = read_u16(archive, local_header + 28)? as usize;
+	let start = local_header + 30 + name_len + extra_len;
+	archive.get(start .. start + compressed_len)
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+	data.get(pos .. pos + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+	data.get(pos .. pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Builds an archive of stored members, for tests.
+#[cfg(test)]
+pub(crate) fn stored_zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+	let mut archive = Vec::new();
+	let mut central = Vec::new();
+
+	for (name, content) in members {
+		let offset = archive.len() as u32;
+		let len = (content.len() as u32).to_le_bytes();
+		let name_len = (name.len() as u16).to_le_bytes();
+
+		archive.extend_from_slice(&LOCAL_HEADER_SIG.to_le_bytes());
+		archive.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+		archive.extend_from_slice(&len);
+		archive.extend_from_slice(&len);
+		archive.extend_from_slice(&name_len);
+		archive.extend_from_slice(&[0, 0]);
+		archive.extend_from_slice(name.as_bytes());
+		archive.extend_from_slice(content);
+
+		central.extend_from_slice(&CENTRAL_HEADER_SIG.to_le_bytes());
+		central.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+		central.extend_from_slice(&len);
+		central.extend_from_slice(&len);
+		central.extend_from_slice(&name_len);
+		central.extend_from_slice(&[0; 12]);
+		central.extend_from_slice(&offset.to_le_bytes());
+		central.extend_from_slice(name.as_bytes());
+	}
+
+	let central_offset = archive.len() as u32;
+	let count = (members.len() as u16).to_le_bytes();
+	archive.extend_from_slice(&central);
+	archive.extend_from_slice(&END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+	archive.extend_from_slice(&[0, 0, 0, 0]);
+	archive.extend_from_slice(&count);
+	archive.extend_from_slice(&count);
+	archive.extend_from_slice(&(central.len() as u32).to_le_bytes());
+	archive.extend_from_slice(&central_offset.to_le_bytes());
+	archive.extend_from_slice(&[0, 0]);
+	archive
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn extract() {
+		let archive = stored_zip(&[("README.TXT", b"read me"), ("games/Elite.SSD", b"disc")]);
+		assert!(is_zip(&archive));
+		assert_eq!(Ok(b"disc".to_vec()), extract_disc_image(&archive, 0x100));
+		assert!(extract_disc_image(&archive, 2).is_err());
+
+		let archive = stored_zip(&[("README.TXT", b"read me")]);
+		assert!(extract_disc_image(&archive, 0x100).is_err());
+		assert!(!is_zip(b"not a zip"));
+	}
+
+	#[test]
+	fn deflated() {
+		let mut archive = stored_zip(&[("a.dsd", b"")]);
+		let content = vec![0xe5u8; 0x1000];
+		let deflated = miniz_oxide::deflate::compress_to_vec(&content, 6);
+
+		// patch in the deflated member, moving the central directory along
+		let split = 30 + 5;
+		let tail = archive.split_off(split);
+		archive.extend_from_slice(&deflated);
+		archive.extend_from_slice(&tail);
+		let eocd = find_end_of_central_dir(&archive).unwrap();
+		let central = split + deflated.len();
+		archive[eocd + 16 .. eocd + 20].copy_from_slice(&(central as u32).to_le_bytes());
+		archive[central + 10] = METHOD_DEFLATED as u8;
+		archive[central + 20 .. central + 24].copy_from_slice(&(deflated.len() as u32).to_le_bytes());
+		archive[central + 24 .. central + 28].copy_from_slice(&(content.len() as u32).to_le_bytes());
+
+		assert_eq!(Ok(content), extract_disc_image(&archive, 0x1000));
+	}
+}